@@ -0,0 +1,124 @@
+//! Account registration and the signed-request plumbing every authenticated ACME call uses.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use directory::Directory;
+use http::{self, Response};
+use helper::{self, SigningKey};
+use error::Result;
+
+/// Builder for registering a new ACME account, returned by `Directory::account_registration`.
+pub struct AccountRegistration {
+    directory: Directory,
+    pkey: Option<PKey>,
+    email: Option<String>,
+}
+
+impl AccountRegistration {
+    pub fn new(directory: Directory) -> AccountRegistration {
+        AccountRegistration { directory: directory, pkey: None, email: None }
+    }
+
+    /// Sets a contact email to register with the account.
+    pub fn email(mut self, email: &str) -> AccountRegistration {
+        self.email = Some(email.to_owned());
+        self
+    }
+
+    /// Uses an existing private key (PEM) instead of generating a new one.
+    pub fn pkey_from_file<P: AsRef<Path>>(mut self, path: P) -> Result<AccountRegistration> {
+        let mut content = Vec::new();
+        File::open(path)?.read_to_end(&mut content)?;
+        self.pkey = Some(PKey::from_rsa(Rsa::private_key_from_pem(&content)?)?);
+        Ok(self)
+    }
+
+    /// Registers the account with the ACME server, generating a key pair first if one
+    /// wasn't supplied via `pkey_from_file`.
+    pub fn register(self) -> Result<Account> {
+        let pkey = match self.pkey {
+            Some(pkey) => pkey,
+            None => PKey::from_rsa(Rsa::generate(2048)?)?,
+        };
+
+        let mut contact = Vec::new();
+        if let Some(email) = self.email {
+            contact.push(format!("\"mailto:{}\"", email));
+        }
+        let payload = format!(
+            "{{\"termsOfServiceAgreed\":true,\"contact\":[{}]}}",
+            contact.join(",")
+        );
+
+        let res = self.directory.request_jwk(&pkey, &self.directory.new_account_url, &payload)?;
+        let kid = res.location.ok_or("account registration response did not include a Location header")?;
+
+        Ok(Account { directory: self.directory, pkey: pkey, kid: kid })
+    }
+}
+
+/// A registered ACME account. Every authenticated request after registration is signed
+/// with this account's key and identified by its `kid` (the account URL).
+pub struct Account {
+    directory: Directory,
+    pkey: PKey,
+    kid: String,
+}
+
+impl Account {
+    /// The account URL ACME calls `kid`, used to identify the signer on every subsequent
+    /// request instead of re-embedding the JWK.
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn pkey(&self) -> &PKey {
+        &self.pkey
+    }
+
+    pub fn directory(&self) -> &Directory {
+        &self.directory
+    }
+
+    /// Saves this account's private key as a PEM file.
+    pub fn save_private_key<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let pem = self.pkey.private_key_to_pem()?;
+        File::create(path)?.write_all(&pem)?;
+        Ok(())
+    }
+
+    /// Signs `payload` with this account's `kid` and POSTs it to `url`, refreshing the
+    /// nonce jar from the response. This is the building block every order/authorization/
+    /// challenge request is made of.
+    pub fn request(&self, url: &str, payload: &str) -> Result<Response> {
+        let nonce = self.directory.take_nonce()?;
+        let jws = helper::jws(&self.pkey, SigningKey::Kid(&self.kid), nonce, url, payload)?;
+        let res = http::post(url, &jws)?;
+        if let Some(nonce) = res.replay_nonce.clone() {
+            self.directory.nonce_jar().update(nonce);
+        }
+        helper::check_status(res)
+    }
+
+    /// POST-as-GET: a signed request with an empty payload, used to fetch orders and
+    /// authorizations once an account exists rather than using a plain unauthenticated GET.
+    pub fn request_as_get(&self, url: &str) -> Result<Response> {
+        self.request(url, "")
+    }
+
+    /// Revokes a signed certificate, reading it from a PEM file and POSTing its DER
+    /// encoding to the directory's `revokeCert` endpoint.
+    pub fn revoke_certificate_from_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        use openssl::x509::X509;
+
+        let mut content = Vec::new();
+        File::open(path)?.read_to_end(&mut content)?;
+        let cert = X509::from_pem(&content)?;
+        let payload = format!("{{\"certificate\":\"{}\"}}", helper::b64(&cert.to_der()?));
+        self.request(&self.directory.revoke_cert_url, &payload)?;
+        Ok(())
+    }
+}
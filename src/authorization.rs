@@ -0,0 +1,180 @@
+//! ACME authorizations and challenges (RFC 8555 sections 7.1.4 and 8).
+
+use std::thread::sleep;
+use std::time::Duration;
+use serde_json::Value;
+use openssl::hash::{hash, MessageDigest};
+use account::Account;
+use error::{Error, Result};
+
+/// One identifier's authorization within an order, holding the challenges available to
+/// prove control of it.
+pub struct Authorization {
+    url: String,
+    status: String,
+    identifier: String,
+    wildcard: bool,
+    challenges: Vec<Challenge>,
+}
+
+impl Authorization {
+    /// POST-as-GETs `url` and parses the resulting authorization object.
+    pub(crate) fn fetch(account: &Account, url: &str) -> Result<Authorization> {
+        let res = account.request_as_get(url)?;
+        let json: Value = ::serde_json::from_str(&res.body).map_err(|e| Error::Json(e.to_string()))?;
+
+        let status = json.get("status")
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::Json("authorization is missing \"status\"".into()))?
+            .to_owned();
+        let identifier = json.get("identifier")
+            .and_then(|v| v.get("value"))
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::Json("authorization is missing \"identifier\"".into()))?
+            .to_owned();
+        let challenges = json.get("challenges")
+            .and_then(Value::as_array)
+            .ok_or_else(|| Error::Json("authorization is missing \"challenges\"".into()))?
+            .iter()
+            .map(Challenge::from_json)
+            .collect::<Result<Vec<_>>>()?;
+        let wildcard = json.get("wildcard").and_then(Value::as_bool).unwrap_or(false);
+
+        Ok(Authorization {
+            url: url.to_owned(),
+            status: status,
+            identifier: identifier,
+            wildcard: wildcard,
+            challenges: challenges,
+        })
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// `true` if this authorization is for a wildcard identifier (e.g. `*.example.org`).
+    /// Per RFC 8555 section 8.3, wildcard identifiers may only be validated via DNS-01;
+    /// the server won't even offer an `http-01` challenge for one.
+    pub fn is_wildcard(&self) -> bool {
+        self.wildcard
+    }
+
+    /// The `http-01` challenge for this authorization, if the server offered one.
+    pub fn get_http_challenge(&self) -> Option<&Challenge> {
+        self.challenges.iter().find(|c| c.ctype == "http-01")
+    }
+
+    /// The `dns-01` challenge for this authorization, if the server offered one.
+    pub fn get_dns_challenge(&self) -> Option<&Challenge> {
+        self.challenges.iter().find(|c| c.ctype == "dns-01")
+    }
+
+    /// Polls this authorization (POST-as-GET) until its status is no longer `pending`,
+    /// sleeping `interval` between attempts, up to `attempts` times. An `invalid` status
+    /// is terminal (RFC 8555 section 7.1.6), mirroring the check `Challenge::validate`
+    /// already makes on the same status field -- it means the authorization is dead and
+    /// will never reach `valid` no matter how many more times this polls.
+    pub fn wait_done(&mut self, account: &Account, interval: Duration, attempts: u32) -> Result<()> {
+        for _ in 0..attempts {
+            if self.status == "invalid" {
+                return Err(Error::Acme(format!(
+                    "authorization for {} failed, status is \"invalid\"",
+                    self.identifier
+                )));
+            }
+            if self.status != "pending" {
+                return Ok(());
+            }
+            sleep(interval);
+            *self = Authorization::fetch(account, &self.url)?;
+        }
+        Err(Error::Acme(format!(
+            "authorization for {} did not finish, last status was \"{}\"",
+            self.identifier,
+            self.status
+        )))
+    }
+}
+
+/// A single challenge offered by an authorization (e.g. `http-01` or `dns-01`).
+pub struct Challenge {
+    url: String,
+    ctype: String,
+    token: String,
+    status: String,
+}
+
+impl Challenge {
+    fn from_json(json: &Value) -> Result<Challenge> {
+        let field = |name: &str| -> Result<String> {
+            json.get(name)
+                .and_then(Value::as_str)
+                .map(|s| s.to_owned())
+                .ok_or_else(|| Error::Json(format!("challenge is missing \"{}\"", name)))
+        };
+        Ok(Challenge {
+            url: field("url")?,
+            ctype: field("type")?,
+            token: field("token")?,
+            status: field("status").unwrap_or_else(|_| "pending".to_owned()),
+        })
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    /// The challenge token, used as the file name under `.well-known/acme-challenge/`.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// The key authorization: `token || "." || base64url(SHA-256(JWK))` (the JWK thumbprint), the
+    /// value this challenge expects the client to publish (as an HTTP file or DNS record).
+    pub fn key_authorization(&self, account: &Account) -> Result<String> {
+        let thumbprint = jwk_thumbprint(account)?;
+        Ok(format!("{}.{}", self.token, thumbprint))
+    }
+
+    /// The signature DNS-01 expects in the `_acme-challenge` TXT record: the base64url
+    /// SHA-256 digest of the key authorization.
+    pub fn dns_txt_value(&self, account: &Account) -> Result<String> {
+        let key_authorization = self.key_authorization(account)?;
+        let digest = hash(MessageDigest::sha256(), key_authorization.as_bytes())?;
+        Ok(::helper::b64(&digest))
+    }
+
+    /// Tells the server to attempt validation, then polls the challenge (every 2 seconds,
+    /// up to 10 times) until it is `valid` or `invalid`. The parent authorization is not
+    /// polled here; `Authorization::wait_done` does that.
+    pub fn validate(&self, account: &Account) -> Result<()> {
+        account.request(&self.url, "{}")?;
+        for _ in 0..10 {
+            sleep(Duration::from_secs(2));
+            let res = account.request_as_get(&self.url)?;
+            let json: Value = ::serde_json::from_str(&res.body).map_err(|e| Error::Json(e.to_string()))?;
+            match json.get("status").and_then(Value::as_str) {
+                Some("valid") => return Ok(()),
+                Some("invalid") => return Err(Error::Acme(format!("challenge for token {} failed", self.token))),
+                _ => continue,
+            }
+        }
+        Err(Error::Acme(format!("challenge for token {} did not validate in time", self.token)))
+    }
+}
+
+fn jwk_thumbprint(account: &Account) -> Result<String> {
+    let rsa = account.pkey().rsa()?;
+    let jwk = format!(
+        "{{\"e\":\"{}\",\"kty\":\"RSA\",\"n\":\"{}\"}}",
+        ::helper::b64(&rsa.e().to_vec()),
+        ::helper::b64(&rsa.n().to_vec())
+    );
+    let digest = hash(MessageDigest::sha256(), jwk.as_bytes())?;
+    Ok(::helper::b64(&digest))
+}
@@ -0,0 +1,253 @@
+//! A managed set of domains that keeps their certificates renewed automatically, turning
+//! the crate from a one-shot `sign` tool into a long-running renewal daemon.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use openssl::x509::X509;
+use openssl::asn1::Asn1Time;
+use error::{Error, Result};
+use revocation::{self, RevocationStatus};
+
+/// Default renewal threshold: reissue once a certificate has less than this long left.
+pub const DEFAULT_RENEW_BEFORE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Default minimum time between rechecking a domain that doesn't need renewal yet. Keeps
+/// a restart from immediately re-checking (and, for a domain with a CRL distribution
+/// point, re-fetching) every managed domain that `state.json` already shows was just
+/// checked.
+pub const DEFAULT_RECHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Issues (or reissues) a certificate for `domain`, returning a PEM-encoded private key
+/// and the PEM certificate chain. Supplied by the caller since the actual validation
+/// method (HTTP-01 webroot, standalone, DNS-01, ...) is a deployment choice, not something
+/// `CertStore` should hardcode.
+pub type Issuer = Box<Fn(&str) -> Result<(Vec<u8>, String)> + Send + Sync>;
+
+/// Notified every time `CertStore` reissues a certificate, so something else holding
+/// those certs in memory (e.g. a rustls `ResolvesServerCert` adapter) can pick up the
+/// rotation without re-reading from disk or restarting.
+pub trait RenewalObserver: Send + Sync {
+    fn on_renewed(&self, domain: &str, key_pem: &[u8], cert_pem_chain: &str);
+}
+
+/// Per-domain bookkeeping, persisted to disk so a restart doesn't re-trigger a renewal
+/// (or re-hammer the ACME server) for a domain that was just checked or renewed.
+#[derive(Default, Clone, Copy)]
+struct DomainState {
+    last_check: Option<u64>,
+    last_renewal: Option<u64>,
+}
+
+/// Keeps a set of managed domains renewed, reissuing each one shortly before its
+/// certificate expires and persisting enough state to survive a restart.
+pub struct CertStore {
+    dir: PathBuf,
+    renew_before: Duration,
+    recheck_interval: Duration,
+    issuer: Issuer,
+    observer: Option<Arc<RenewalObserver>>,
+    domains: HashMap<String, DomainState>,
+}
+
+impl CertStore {
+    /// Opens (or creates) a certificate store rooted at `dir`, loading any persisted
+    /// per-domain state from `dir/state.json`.
+    pub fn new<P: AsRef<Path>>(dir: P, issuer: Issuer) -> Result<CertStore> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let domains = CertStore::load_state(&dir)?;
+        Ok(CertStore {
+            dir: dir,
+            renew_before: DEFAULT_RENEW_BEFORE,
+            recheck_interval: DEFAULT_RECHECK_INTERVAL,
+            issuer: issuer,
+            observer: None,
+            domains: domains,
+        })
+    }
+
+    /// Overrides the default 30 day renewal threshold.
+    pub fn renew_before(mut self, renew_before: Duration) -> CertStore {
+        self.renew_before = renew_before;
+        self
+    }
+
+    /// Overrides the default 1 hour minimum time between rechecking a domain that didn't
+    /// need renewal last time.
+    pub fn recheck_interval(mut self, recheck_interval: Duration) -> CertStore {
+        self.recheck_interval = recheck_interval;
+        self
+    }
+
+    /// Registers something to notify on every renewal, e.g. a rustls cert resolver that
+    /// wants to serve the new certificate immediately instead of waiting for a restart.
+    pub fn observe(mut self, observer: Arc<RenewalObserver>) -> CertStore {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Adds `domain` to the managed set. A no-op if it's already managed.
+    pub fn add_domain(&mut self, domain: &str) {
+        self.domains.entry(domain.to_owned()).or_insert_with(DomainState::default);
+    }
+
+    /// Lazily adds a domain the first time it's seen, triggered by `hostname` matching an
+    /// `OnDemandDomains` pattern. Subsequent calls that match the same pattern are a
+    /// no-op, since a single wildcard cert already covers every hostname it matches.
+    /// Errors if `hostname` doesn't match any registered pattern, so a caller driving this
+    /// from an auth hook (e.g. a reverse proxy) can tell an unauthorized host apart from
+    /// one whose certificate is simply already on hand.
+    pub fn ensure_on_demand(&mut self, hostname: &str, on_demand: &::on_demand::OnDemandDomains) -> Result<()> {
+        let wildcard = on_demand.wildcard_for(hostname)
+            .ok_or_else(|| Error::Other(format!("{} does not match any on-demand pattern", hostname)))??;
+        self.add_domain(&wildcard);
+        Ok(())
+    }
+
+    fn file_name(domain: &str) -> String {
+        domain.replace('*', "_wildcard_")
+    }
+
+    fn cert_path(&self, domain: &str) -> PathBuf {
+        self.dir.join(format!("{}.crt", CertStore::file_name(domain)))
+    }
+
+    fn key_path(&self, domain: &str) -> PathBuf {
+        self.dir.join(format!("{}.key", CertStore::file_name(domain)))
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.dir.join("state.json")
+    }
+
+    fn load_state(dir: &Path) -> Result<HashMap<String, DomainState>> {
+        let path = dir.join("state.json");
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        let json: Value = ::serde_json::from_str(&content).map_err(|e| Error::Json(e.to_string()))?;
+        let mut domains = HashMap::new();
+        if let Some(map) = json.as_object() {
+            for (domain, state) in map {
+                domains.insert(domain.clone(), DomainState {
+                    last_check: state.get("last_check").and_then(Value::as_u64),
+                    last_renewal: state.get("last_renewal").and_then(Value::as_u64),
+                });
+            }
+        }
+        Ok(domains)
+    }
+
+    fn save_state(&self) -> Result<()> {
+        let mut entries = Vec::new();
+        for (domain, state) in &self.domains {
+            entries.push(format!(
+                "\"{}\":{{\"last_check\":{},\"last_renewal\":{}}}",
+                domain,
+                state.last_check.map(|t| t.to_string()).unwrap_or_else(|| "null".to_owned()),
+                state.last_renewal.map(|t| t.to_string()).unwrap_or_else(|| "null".to_owned())
+            ));
+        }
+        let json = format!("{{{}}}", entries.join(","));
+        File::create(self.state_path())?.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// `true` if `domain` has no certificate yet, its certificate's `notAfter` is within
+    /// `self.renew_before` of now, or its certificate has been revoked. A revoked cert
+    /// needs reissuing immediately, no matter how much validity it has left, so it's
+    /// checked here rather than left to whatever is consuming `needs_renewal`.
+    fn needs_renewal(&self, domain: &str) -> Result<bool> {
+        let path = self.cert_path(domain);
+        if !path.exists() {
+            return Ok(true);
+        }
+        let mut content = Vec::new();
+        File::open(path)?.read_to_end(&mut content)?;
+        let cert = X509::from_pem(&content)?;
+
+        if revocation::check(&cert).unwrap_or(RevocationStatus::Unknown) == RevocationStatus::Revoked {
+            return Ok(true);
+        }
+
+        let threshold = Asn1Time::days_from_now((self.renew_before.as_secs() / (24 * 60 * 60)) as u32)?;
+        Ok(cert.not_after() < threshold.as_ref())
+    }
+
+    /// Reissues `domain` via `self.issuer` and atomically replaces its on-disk cert/key
+    /// by writing to a temporary file and renaming over the original.
+    fn renew(&mut self, domain: &str) -> Result<()> {
+        let (key_pem, cert_pem) = (self.issuer)(domain)?;
+
+        let key_tmp = self.key_path(domain).with_extension("key.tmp");
+        File::create(&key_tmp)?.write_all(&key_pem)?;
+        fs::rename(&key_tmp, self.key_path(domain))?;
+
+        let cert_tmp = self.cert_path(domain).with_extension("crt.tmp");
+        File::create(&cert_tmp)?.write_all(cert_pem.as_bytes())?;
+        fs::rename(&cert_tmp, self.cert_path(domain))?;
+
+        if let Some(ref observer) = self.observer {
+            observer.on_renewed(domain, &key_pem, &cert_pem);
+        }
+
+        let now = now_secs();
+        let state = self.domains.entry(domain.to_owned()).or_insert_with(DomainState::default);
+        state.last_check = Some(now);
+        state.last_renewal = Some(now);
+        Ok(())
+    }
+
+    /// Checks every managed domain once, renewing any that need it. A domain checked
+    /// within the last `recheck_interval` is skipped, so a restart doesn't immediately
+    /// re-check (and, for a domain with a CRL distribution point, re-fetch) every managed
+    /// domain `state.json` already shows was just checked. A domain whose renewal fails is
+    /// logged and skipped rather than aborting the whole pass, so one broken domain
+    /// doesn't stop the others from renewing.
+    pub fn check_all(&mut self) -> Result<()> {
+        let domains: Vec<String> = self.domains.keys().cloned().collect();
+        let now = now_secs();
+        for domain in domains {
+            let last_check = self.domains.get(&domain).and_then(|s| s.last_check);
+            if let Some(last_check) = last_check {
+                if now.saturating_sub(last_check) < self.recheck_interval.as_secs() {
+                    continue;
+                }
+            }
+            match self.needs_renewal(&domain) {
+                Ok(true) => {
+                    if let Err(err) = self.renew(&domain) {
+                        error!("failed to renew certificate for {}: {}", domain, err);
+                    }
+                }
+                Ok(false) => {
+                    self.domains.get_mut(&domain).unwrap().last_check = Some(now);
+                }
+                Err(err) => error!("failed to check certificate for {}: {}", domain, err),
+            }
+        }
+        self.save_state()
+    }
+
+    /// Runs the renewal loop forever, checking every domain once per `interval` (the
+    /// `renew` subcommand passes an hour). Intended to be the body of a long-running
+    /// `renew` subcommand.
+    pub fn run(&mut self, interval: Duration) -> Result<()> {
+        loop {
+            self.check_all()?;
+            sleep(interval);
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,136 @@
+//! CSR generation and the signed certificate returned once an order is finalized.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::x509::{X509Req, X509ReqBuilder, X509Name};
+use openssl::hash::MessageDigest;
+use openssl::nid;
+use openssl::stack::Stack;
+use openssl::x509::extension::SubjectAlternativeName;
+use error::Result;
+
+/// Builds the domain private key and CSR an order is finalized with.
+pub struct CertificateSigner {
+    pkey: Option<PKey>,
+    csr: Option<X509Req>,
+}
+
+impl CertificateSigner {
+    pub fn new() -> CertificateSigner {
+        CertificateSigner { pkey: None, csr: None }
+    }
+
+    /// Uses an existing domain private key (PEM) instead of generating a new one.
+    pub fn pkey_from_file<P: AsRef<Path>>(mut self, path: P) -> Result<CertificateSigner> {
+        let mut content = Vec::new();
+        File::open(path)?.read_to_end(&mut content)?;
+        self.pkey = Some(PKey::from_rsa(Rsa::private_key_from_pem(&content)?)?);
+        Ok(self)
+    }
+
+    /// Uses an existing private key and CSR (both PEM) instead of generating them. The
+    /// identifiers in the CSR must match the order's.
+    pub fn csr_from_file<P: AsRef<Path>>(mut self, key_path: P, csr_path: P) -> Result<CertificateSigner> {
+        self = self.pkey_from_file(key_path)?;
+        let mut content = Vec::new();
+        File::open(csr_path)?.read_to_end(&mut content)?;
+        self.csr = Some(X509Req::from_pem(&content)?);
+        Ok(self)
+    }
+
+    /// Generates (or reuses) a CSR covering `domains`, generating a 2048-bit RSA key
+    /// first if one wasn't supplied. Returns the DER encoding the order's `finalize`
+    /// endpoint expects.
+    pub fn generate_csr(&mut self, domains: &[&str]) -> Result<Vec<u8>> {
+        if self.csr.is_none() {
+            let pkey = match self.pkey.take() {
+                Some(pkey) => pkey,
+                None => PKey::from_rsa(Rsa::generate(2048)?)?,
+            };
+
+            let mut builder = X509ReqBuilder::new()?;
+            builder.set_pubkey(&pkey)?;
+
+            let mut name = X509Name::builder()?;
+            name.append_entry_by_nid(nid::COMMONNAME, domains[0])?;
+            builder.set_subject_name(&name.build())?;
+
+            let mut extensions = Stack::new()?;
+            let mut san = SubjectAlternativeName::new();
+            for domain in domains {
+                san.dns(domain);
+            }
+            extensions.push(san.build(&builder.x509v3_context(None))?)?;
+            builder.add_extensions(&extensions)?;
+
+            builder.sign(&pkey, MessageDigest::sha256())?;
+
+            self.pkey = Some(pkey);
+            self.csr = Some(builder.build());
+        }
+
+        Ok(self.csr.as_ref().unwrap().to_der()?)
+    }
+
+    pub fn pkey(&self) -> Option<&PKey> {
+        self.pkey.as_ref()
+    }
+
+    pub fn csr(&self) -> Option<&X509Req> {
+        self.csr.as_ref()
+    }
+
+    /// Consumes the signer, handing back the key/CSR it generated (or was given) so they
+    /// can be wrapped into a `Certificate` alongside the downloaded chain.
+    pub fn into_parts(self) -> (Option<PKey>, Option<X509Req>) {
+        (self.pkey, self.csr)
+    }
+}
+
+/// A finalized, signed certificate downloaded from an order: the full PEM chain plus the
+/// key/CSR it was generated with.
+pub struct Certificate {
+    pkey: Option<PKey>,
+    csr: Option<X509Req>,
+    pem_chain: String,
+}
+
+impl Certificate {
+    pub fn new(pkey: Option<PKey>, csr: Option<X509Req>, pem_chain: String) -> Certificate {
+        Certificate { pkey: pkey, csr: csr, pem_chain: pem_chain }
+    }
+
+    /// Saves just the leaf certificate (the first PEM block in the chain).
+    pub fn save_signed_certificate<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let leaf = self.pem_chain
+            .split("-----END CERTIFICATE-----")
+            .next()
+            .map(|s| format!("{}-----END CERTIFICATE-----\n", s))
+            .unwrap_or_default();
+        File::create(path)?.write_all(leaf.as_bytes())?;
+        Ok(())
+    }
+
+    /// Saves the full certificate chain as returned by the ACME server. `_intermediate`
+    /// is accepted for API compatibility with the old per-domain signer but is ignored:
+    /// ACME v2 already returns the complete chain from the `certificate` URL.
+    pub fn save_signed_certificate_and_chain<P: AsRef<Path>>(&self, _intermediate: Option<&str>, path: P) -> Result<()> {
+        File::create(path)?.write_all(self.pem_chain.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn save_private_key<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let pkey = self.pkey.as_ref().ok_or("no private key to save")?;
+        File::create(path)?.write_all(&pkey.private_key_to_pem()?)?;
+        Ok(())
+    }
+
+    pub fn save_csr<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let csr = self.csr.as_ref().ok_or("no CSR to save")?;
+        File::create(path)?.write_all(&csr.to_pem()?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! The ACME directory: the well-known entry point that advertises every other endpoint
+//! and is the root of the `Directory -> Account -> Order -> Authorization -> Challenge`
+//! chain the rest of the crate is built around.
+
+use std::sync::Arc;
+use serde_json::Value;
+use openssl::pkey::PKey;
+use account::AccountRegistration;
+use order::Order;
+use account::Account;
+use http::{self, Response};
+use helper::{self, NonceJar, SigningKey};
+use error::{Error, Result};
+
+/// Let's Encrypt's production ACME v2 (RFC 8555) directory.
+pub const LETS_ENCRYPT_DIRECTORY_URL: &'static str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// Let's Encrypt's staging ACME v2 directory, useful for testing against rate-limit-free
+/// infrastructure that issues certificates signed by an untrusted root.
+pub const LETS_ENCRYPT_STAGING_DIRECTORY_URL: &'static str =
+    "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+/// An ACME v2 directory: the set of endpoint URLs a server advertises, plus the nonce
+/// jar every signed request shares.
+#[derive(Clone)]
+pub struct Directory {
+    pub(crate) new_nonce_url: String,
+    pub(crate) new_account_url: String,
+    pub(crate) new_order_url: String,
+    pub(crate) revoke_cert_url: String,
+    nonce_jar: Arc<NonceJar>,
+}
+
+impl Directory {
+    /// Fetches and parses the directory object served at `url`.
+    pub fn from_url(url: &str) -> Result<Directory> {
+        let res = http::get(url)?;
+        let json: Value = ::serde_json::from_str(&res.body)
+            .map_err(|e| Error::Json(e.to_string()))?;
+
+        let field = |name: &str| -> Result<String> {
+            json.get(name)
+                .and_then(Value::as_str)
+                .map(|s| s.to_owned())
+                .ok_or_else(|| Error::Json(format!("directory is missing \"{}\"", name)))
+        };
+
+        Ok(Directory {
+            new_nonce_url: field("newNonce")?,
+            new_account_url: field("newAccount")?,
+            new_order_url: field("newOrder")?,
+            revoke_cert_url: field("revokeCert")?,
+            nonce_jar: Arc::new(NonceJar::new()),
+        })
+    }
+
+    /// Let's Encrypt's production ACME v2 directory.
+    pub fn lets_encrypt() -> Result<Directory> {
+        Directory::from_url(LETS_ENCRYPT_DIRECTORY_URL)
+    }
+
+    /// Let's Encrypt's staging ACME v2 directory.
+    pub fn lets_encrypt_staging() -> Result<Directory> {
+        Directory::from_url(LETS_ENCRYPT_STAGING_DIRECTORY_URL)
+    }
+
+    /// Starts building a new account registration.
+    pub fn account_registration(&self) -> AccountRegistration {
+        AccountRegistration::new(self.clone())
+    }
+
+    /// Creates a new order covering `domains`, which becomes a single certificate with
+    /// every domain as a SAN. This is the ACME v2 replacement for the old per-domain
+    /// `account.authorization(domain)` + `certificate_signer` flow.
+    pub fn new_order(&self, account: &Account, domains: &[&str]) -> Result<Order> {
+        let identifiers: Vec<String> = domains.iter()
+            .map(|d| format!("{{\"type\":\"dns\",\"value\":\"{}\"}}", d))
+            .collect();
+        let payload = format!("{{\"identifiers\":[{}]}}", identifiers.join(","));
+
+        let res = account.request(&self.new_order_url, &payload)?;
+        let order_url = res.location.clone()
+            .ok_or("new-order response did not include a Location header")?;
+        Order::from_response(order_url, &res)
+    }
+
+    /// Returns (and consumes) the most recently seen `Replay-Nonce`, fetching a fresh one
+    /// from `newNonce` if the jar is currently empty.
+    pub fn take_nonce(&self) -> Result<String> {
+        if let Ok(nonce) = self.nonce_jar.take() {
+            return Ok(nonce);
+        }
+        let res = http::head(&self.new_nonce_url)?;
+        res.replay_nonce.ok_or_else(|| "new-nonce response did not include a Replay-Nonce header".into())
+    }
+
+    pub(crate) fn nonce_jar(&self) -> &NonceJar {
+        &self.nonce_jar
+    }
+
+    /// Signs `payload` with an embedded JWK (rather than a `kid`) and POSTs it to `url`.
+    /// Only ever used for `newAccount`, before the account (and its `kid`) exists.
+    pub fn request_jwk(&self, pkey: &PKey, url: &str, payload: &str) -> Result<Response> {
+        let nonce = self.take_nonce()?;
+        let jws = helper::jws(pkey, SigningKey::Jwk(pkey), nonce, url, payload)?;
+        let res = http::post(url, &jws)?;
+        if let Some(nonce) = res.replay_nonce.clone() {
+            self.nonce_jar.update(nonce);
+        }
+        helper::check_status(res)
+    }
+}
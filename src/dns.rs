@@ -0,0 +1,188 @@
+//! Pluggable DNS-01 providers, so `sign_certificate` can publish `_acme-challenge` TXT
+//! records automatically instead of blocking on a human to create them by hand.
+
+use std::io;
+use std::net::IpAddr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use serde_json::Value;
+use trust_dns_resolver::Resolver;
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use error::{Error, Result};
+
+/// Publishes and removes the TXT record a DNS-01 challenge expects at
+/// `_acme-challenge.<domain>`. Implement this against whatever DNS host manages the
+/// zone; `ManualDnsProvider` is the fallback for everyone else.
+pub trait DnsProvider {
+    /// Creates (or updates) the TXT record named `fqdn` (the full name, e.g.
+    /// `_acme-challenge.example.org`) to hold `value`.
+    fn set_txt_record(&self, fqdn: &str, value: &str) -> Result<()>;
+
+    /// Removes the TXT record `set_txt_record` published.
+    fn clear_txt_record(&self, fqdn: &str, value: &str) -> Result<()>;
+}
+
+/// Finds the nameservers authoritative for the zone containing `fqdn`, using the system
+/// resolver to walk up from the full name (since `_acme-challenge.sub.example.org` is
+/// itself never a zone apex) until an NS lookup succeeds.
+fn authoritative_name_servers(fqdn: &str, system_resolver: &Resolver) -> Result<Vec<IpAddr>> {
+    let labels: Vec<&str> = fqdn.trim_end_matches('.').split('.').collect();
+    for start in 0..labels.len().saturating_sub(1) {
+        let zone = labels[start..].join(".");
+        if let Ok(ns_lookup) = system_resolver.ns_lookup(zone.as_str()) {
+            let mut ips = Vec::new();
+            for ns in ns_lookup.iter() {
+                if let Ok(a_lookup) = system_resolver.lookup_ip(ns.to_string().as_str()) {
+                    ips.extend(a_lookup.iter());
+                }
+            }
+            if !ips.is_empty() {
+                return Ok(ips);
+            }
+        }
+    }
+    Err(Error::Other(format!("could not find an authoritative nameserver for {}", fqdn)))
+}
+
+/// Polls the zone's own authoritative nameservers directly for the TXT record at `fqdn`
+/// until it contains `value` or `timeout` elapses. Querying a caching recursive resolver
+/// here (the default a plain `Resolver::new` would use) risks a stale negative answer
+/// sitting in its cache for the record's TTL, which is exactly the false-timeout this is
+/// meant to avoid.
+pub fn wait_for_propagation(fqdn: &str, value: &str, timeout: Duration, poll_interval: Duration) -> Result<()> {
+    let system_resolver = Resolver::new(ResolverConfig::default(), ResolverOpts::default())
+        .map_err(|e| Error::Other(format!("could not create DNS resolver: {}", e)))?;
+    let name_server_ips = authoritative_name_servers(fqdn, &system_resolver)?;
+
+    let config = ResolverConfig::from_parts(
+        None,
+        Vec::new(),
+        NameServerConfigGroup::from_ips_clear(&name_server_ips, 53),
+    );
+    // Disabled so every poll re-queries the authoritative server rather than serving a
+    // stale answer back out of this resolver's own cache.
+    let opts = ResolverOpts { cache_size: 0, ..ResolverOpts::default() };
+    let resolver = Resolver::new(config, opts)
+        .map_err(|e| Error::Other(format!("could not create authoritative DNS resolver: {}", e)))?;
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let found = resolver.txt_lookup(fqdn)
+            .map(|lookup| lookup.iter().any(|txt| txt.to_string() == value))
+            .unwrap_or(false);
+        if found {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(Error::Acme(format!(
+                "TXT record for {} did not propagate within {:?}",
+                fqdn,
+                timeout
+            )));
+        }
+        sleep(poll_interval);
+    }
+}
+
+/// The original interactive behavior: print the record to create and block until the
+/// user presses enter. Used when no `--dns-provider` is configured.
+pub struct ManualDnsProvider;
+
+impl DnsProvider for ManualDnsProvider {
+    fn set_txt_record(&self, fqdn: &str, value: &str) -> Result<()> {
+        println!("Please create a TXT record for {}: {}\nPress enter to continue", fqdn, value);
+        io::stdin().read_line(&mut String::new())?;
+        Ok(())
+    }
+
+    fn clear_txt_record(&self, _fqdn: &str, _value: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Publishes TXT records through the Cloudflare DNS API.
+pub struct CloudflareDnsProvider {
+    api_token: String,
+    zone_id: String,
+}
+
+impl CloudflareDnsProvider {
+    pub fn new(api_token: &str, zone_id: &str) -> CloudflareDnsProvider {
+        CloudflareDnsProvider { api_token: api_token.to_owned(), zone_id: zone_id.to_owned() }
+    }
+
+    fn records_url(&self) -> String {
+        format!("https://api.cloudflare.com/client/v4/zones/{}/dns_records", self.zone_id)
+    }
+
+    fn find_record_id(&self, fqdn: &str, value: &str) -> Result<Option<String>> {
+        let client = ::reqwest::Client::new();
+        let mut res = client.get(&self.records_url())
+            .bearer_auth(&self.api_token)
+            .query(&[("type", "TXT"), ("name", fqdn), ("content", value)])
+            .send()
+            .map_err(|e| Error::Http(e.to_string()))?;
+        let body = res.text().map_err(|e| Error::Http(e.to_string()))?;
+        let json = check_cloudflare_response(res.status().as_u16(), &body)?;
+        Ok(json.get("result")
+            .and_then(Value::as_array)
+            .and_then(|results| results.get(0))
+            .and_then(|record| record.get("id"))
+            .and_then(Value::as_str)
+            .map(str::to_owned))
+    }
+}
+
+impl DnsProvider for CloudflareDnsProvider {
+    fn set_txt_record(&self, fqdn: &str, value: &str) -> Result<()> {
+        let client = ::reqwest::Client::new();
+        let payload = format!(
+            "{{\"type\":\"TXT\",\"name\":\"{}\",\"content\":\"{}\",\"ttl\":120}}",
+            fqdn,
+            value
+        );
+        let mut res = client.post(&self.records_url())
+            .bearer_auth(&self.api_token)
+            .body(payload)
+            .send()
+            .map_err(|e| Error::Http(e.to_string()))?;
+        let body = res.text().map_err(|e| Error::Http(e.to_string()))?;
+        check_cloudflare_response(res.status().as_u16(), &body)?;
+        Ok(())
+    }
+
+    fn clear_txt_record(&self, fqdn: &str, value: &str) -> Result<()> {
+        if let Some(record_id) = self.find_record_id(fqdn, value)? {
+            let client = ::reqwest::Client::new();
+            let mut res = client.delete(&format!("{}/{}", self.records_url(), record_id))
+                .bearer_auth(&self.api_token)
+                .send()
+                .map_err(|e| Error::Http(e.to_string()))?;
+            let body = res.text().map_err(|e| Error::Http(e.to_string()))?;
+            check_cloudflare_response(res.status().as_u16(), &body)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a Cloudflare API response body, turning a non-2xx status or a `"success":
+/// false` body into an error carrying Cloudflare's own error messages (bad token, rate
+/// limit, zone mismatch, ...) instead of silently discarding the response and letting
+/// the caller burn the full propagation timeout on a record that was never created.
+fn check_cloudflare_response(status: u16, body: &str) -> Result<Value> {
+    let json: Value = ::serde_json::from_str(body).map_err(|e| Error::Json(e.to_string()))?;
+    let success = json.get("success").and_then(Value::as_bool).unwrap_or(status < 300);
+    if status >= 300 || !success {
+        let errors = json.get("errors")
+            .and_then(Value::as_array)
+            .map(|errors| {
+                errors.iter()
+                    .filter_map(|e| e.get("message").and_then(Value::as_str))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            })
+            .unwrap_or_default();
+        return Err(Error::Http(format!("Cloudflare API error ({}): {}", status, errors)));
+    }
+    Ok(json)
+}
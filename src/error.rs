@@ -0,0 +1,76 @@
+//! Error types used throughout acme-client
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use openssl::error::ErrorStack;
+
+/// A `Result` alias where the `Err` case is `acme_client::Error`.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// Error type for acme-client operations
+#[derive(Debug)]
+pub enum Error {
+    /// An ACME server returned a problem document
+    Acme(String),
+    /// IO error
+    Io(io::Error),
+    /// OpenSSL error
+    Ssl(ErrorStack),
+    /// JSON (de)serialization error
+    Json(String),
+    /// HTTP client error
+    Http(String),
+    /// Other, usually from a `&str`/`String` via `.ok_or(..)?`
+    Other(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Acme(ref msg) => write!(f, "ACME server error: {}", msg),
+            Error::Io(ref err) => write!(f, "IO error: {}", err),
+            Error::Ssl(ref err) => write!(f, "OpenSSL error: {}", err),
+            Error::Json(ref msg) => write!(f, "JSON error: {}", msg),
+            Error::Http(ref msg) => write!(f, "HTTP error: {}", msg),
+            Error::Other(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Acme(ref msg) => msg,
+            Error::Io(ref err) => err.description(),
+            Error::Ssl(ref err) => err.description(),
+            Error::Json(ref msg) => msg,
+            Error::Http(ref msg) => msg,
+            Error::Other(ref msg) => msg,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<ErrorStack> for Error {
+    fn from(err: ErrorStack) -> Error {
+        Error::Ssl(err)
+    }
+}
+
+impl<'a> From<&'a str> for Error {
+    fn from(msg: &'a str) -> Error {
+        Error::Other(msg.to_owned())
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Error {
+        Error::Other(msg)
+    }
+}
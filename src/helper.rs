@@ -0,0 +1,109 @@
+//! Internal helpers shared between the directory, order and authorization modules.
+//!
+//! This mostly deals with the JWS envelope ACME requires around every request: base64url
+//! encoding, building the protected header (either an embedded JWK or a `kid`), and keeping
+//! track of the `Replay-Nonce` the server hands back on every response.
+
+use std::sync::Mutex;
+use openssl::pkey::PKey;
+use openssl::hash::MessageDigest;
+use openssl::sign::Signer;
+use serde_json::Value;
+use http::Response;
+use error::{Error, Result};
+
+/// Base64url encodes `data` without padding, as required by the JOSE spec.
+pub fn b64(data: &[u8]) -> String {
+    ::base64::encode_config(data, ::base64::URL_SAFE_NO_PAD)
+}
+
+/// Holds the most recently seen `Replay-Nonce`, refreshed from every ACME response and
+/// consumed by the next signed request. ACME requires a fresh nonce per request, so this
+/// needs interior mutability since it's shared across `&self` methods.
+pub struct NonceJar {
+    current: Mutex<Option<String>>,
+}
+
+impl NonceJar {
+    pub fn new() -> NonceJar {
+        NonceJar { current: Mutex::new(None) }
+    }
+
+    /// Replaces the stored nonce with one freshly received from the server.
+    pub fn update(&self, nonce: String) {
+        *self.current.lock().unwrap() = Some(nonce);
+    }
+
+    /// Takes the stored nonce, leaving nothing behind — every nonce may only be used once.
+    pub fn take(&self) -> Result<String> {
+        self.current
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| "no Replay-Nonce available, fetch one from new-nonce first".into())
+    }
+}
+
+/// The two ways an ACME request can identify its signer: an embedded JWK (account creation
+/// only) or the account's `kid` URL (every request afterwards).
+pub enum SigningKey<'a> {
+    Jwk(&'a PKey),
+    Kid(&'a str),
+}
+
+/// Builds and signs the JWS envelope ACME expects: `{protected, payload, signature}`, all
+/// base64url encoded, signed over `protected || "." || payload` with RS256.
+pub fn jws(pkey: &PKey, key: SigningKey, nonce: String, url: &str, payload: &str) -> Result<String> {
+    let protected = match key {
+        SigningKey::Jwk(jwk) => format!(
+            "{{\"alg\":\"RS256\",\"jwk\":{},\"nonce\":\"{}\",\"url\":\"{}\"}}",
+            jwk_json(jwk)?,
+            nonce,
+            url
+        ),
+        SigningKey::Kid(kid) => format!(
+            "{{\"alg\":\"RS256\",\"kid\":\"{}\",\"nonce\":\"{}\",\"url\":\"{}\"}}",
+            kid,
+            nonce,
+            url
+        ),
+    };
+
+    let protected_b64 = b64(protected.as_bytes());
+    let payload_b64 = b64(payload.as_bytes());
+    let signing_input = format!("{}.{}", protected_b64, payload_b64);
+
+    let mut signer = Signer::new(MessageDigest::sha256(), pkey)?;
+    signer.update(signing_input.as_bytes())?;
+    let signature = signer.sign_to_vec()?;
+
+    Ok(format!(
+        "{{\"protected\":\"{}\",\"payload\":\"{}\",\"signature\":\"{}\"}}",
+        protected_b64,
+        payload_b64,
+        b64(&signature)
+    ))
+}
+
+/// Turns a non-2xx ACME response into `Error::Acme`, pulling the `detail` out of the
+/// `application/problem+json` body (RFC 7807) the server sends on failure (rate limits,
+/// failed validation, CAA denial, ...) instead of letting a generic "missing field" error
+/// from downstream JSON parsing hide what actually went wrong.
+pub fn check_status(res: Response) -> Result<Response> {
+    if res.status < 200 || res.status >= 300 {
+        let detail = ::serde_json::from_str::<Value>(&res.body).ok()
+            .and_then(|json| json.get("detail").and_then(Value::as_str).map(|s| s.to_owned()));
+        return Err(Error::Acme(detail.unwrap_or(res.body)));
+    }
+    Ok(res)
+}
+
+/// Renders an RSA public key as a JWK JSON object (the `n`/`e` components, base64url encoded).
+fn jwk_json(pkey: &PKey) -> Result<String> {
+    let rsa = pkey.rsa()?;
+    Ok(format!(
+        "{{\"e\":\"{}\",\"kty\":\"RSA\",\"n\":\"{}\"}}",
+        b64(&rsa.e().to_vec()),
+        b64(&rsa.n().to_vec())
+    ))
+}
@@ -0,0 +1,78 @@
+//! Thin wrapper around the HTTP client so the rest of the crate only ever deals with
+//! response bodies, status codes and the handful of headers ACME cares about.
+
+use reqwest;
+use error::{Error, Result};
+
+/// The bits of an ACME HTTP response every caller needs: the body, the status, and the
+/// two headers the protocol threads through every exchange.
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+    pub replay_nonce: Option<String>,
+    pub location: Option<String>,
+}
+
+fn to_response(mut res: reqwest::Response) -> Result<Response> {
+    let replay_nonce = res.headers()
+        .get_raw("Replay-Nonce")
+        .and_then(|v| v.one())
+        .map(|v| String::from_utf8_lossy(v).into_owned());
+    let location = res.headers()
+        .get_raw("Location")
+        .and_then(|v| v.one())
+        .map(|v| String::from_utf8_lossy(v).into_owned());
+    let status = res.status().as_u16();
+    let body = res.text().map_err(|e| Error::Http(e.to_string()))?;
+    Ok(Response { status: status, body: body, replay_nonce: replay_nonce, location: location })
+}
+
+/// `GET url`. Used for fetching the directory and, before RFC 8555, plain-GET authorizations.
+pub fn get(url: &str) -> Result<Response> {
+    let res = reqwest::get(url).map_err(|e| Error::Http(e.to_string()))?;
+    to_response(res)
+}
+
+/// `GET url`, returning the raw response bytes instead of decoding them as text. Used for
+/// binary downloads such as DER-encoded CRLs.
+pub fn get_bytes(url: &str) -> Result<Vec<u8>> {
+    let mut res = reqwest::get(url).map_err(|e| Error::Http(e.to_string()))?;
+    let mut bytes = Vec::new();
+    ::std::io::copy(&mut res, &mut bytes).map_err(|e| Error::Http(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// `HEAD url`, used to get a fresh `Replay-Nonce` from the `new-nonce` endpoint without
+/// spending a full request.
+pub fn head(url: &str) -> Result<Response> {
+    let client = reqwest::Client::new();
+    let res = client.head(url).send().map_err(|e| Error::Http(e.to_string()))?;
+    to_response(res)
+}
+
+/// `POST url` with a `application/jose+json` body, used for every signed ACME request
+/// (including POST-as-GET, which uses an empty payload).
+pub fn post(url: &str, jws_body: &str) -> Result<Response> {
+    let client = reqwest::Client::new();
+    let res = client.post(url)
+        .header(reqwest::header::ContentType("application/jose+json".parse().unwrap()))
+        .body(jws_body.to_owned())
+        .send()
+        .map_err(|e| Error::Http(e.to_string()))?;
+    to_response(res)
+}
+
+/// `POST url` with a raw binary body under `content_type`, returning the raw response
+/// bytes instead of decoding them as text. Used for DER-encoded OCSP requests/responses,
+/// which (unlike every other exchange in this crate) are neither JSON nor JWS.
+pub fn post_bytes(url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>> {
+    let client = reqwest::Client::new();
+    let mut res = client.post(url)
+        .header(reqwest::header::ContentType(content_type.parse().unwrap()))
+        .body(body.to_owned())
+        .send()
+        .map_err(|e| Error::Http(e.to_string()))?;
+    let mut bytes = Vec::new();
+    ::std::io::copy(&mut res, &mut bytes).map_err(|e| Error::Http(e.to_string()))?;
+    Ok(bytes)
+}
@@ -0,0 +1,46 @@
+//! acme-client: a Let's Encrypt / ACME v2 (RFC 8555) client library.
+//!
+//! The typical flow is `Directory -> AccountRegistration -> Account -> Order ->
+//! Authorization -> Challenge`, finishing with `Order::finalize` and
+//! `Order::download_certificate`.
+
+extern crate openssl;
+extern crate openssl_sys;
+extern crate reqwest;
+extern crate serde_json;
+extern crate base64;
+extern crate trust_dns_resolver;
+extern crate glob;
+#[macro_use]
+extern crate log;
+#[cfg(feature = "rustls")]
+extern crate rustls;
+
+pub mod error;
+mod helper;
+mod http;
+pub mod directory;
+pub mod account;
+pub mod authorization;
+pub mod order;
+pub mod certificate;
+pub mod cert_store;
+pub mod standalone;
+pub mod dns;
+pub mod on_demand;
+pub mod revocation;
+#[cfg(feature = "rustls")]
+pub mod resolver;
+
+pub use directory::Directory;
+pub use account::{Account, AccountRegistration};
+pub use authorization::{Authorization, Challenge};
+pub use order::Order;
+pub use certificate::{Certificate, CertificateSigner};
+pub use cert_store::{CertStore, RenewalObserver};
+pub use standalone::StandaloneServer;
+pub use dns::{DnsProvider, ManualDnsProvider, CloudflareDnsProvider};
+pub use on_demand::OnDemandDomains;
+pub use revocation::RevocationStatus;
+#[cfg(feature = "rustls")]
+pub use resolver::CertResolver;
@@ -7,14 +7,37 @@ extern crate env_logger;
 extern crate openssl_sys;
 
 
-use std::io;
 use std::path::Path;
+use std::time::Duration;
 use std::collections::HashSet;
-use acme_client::Directory;
+use acme_client::{Directory, Challenge, Account, CertificateSigner, Certificate, StandaloneServer};
+use acme_client::dns::{DnsProvider, ManualDnsProvider, CloudflareDnsProvider};
+use acme_client::OnDemandDomains;
+use acme_client::revocation::{self, RevocationStatus};
 use acme_client::error::Result;
 use clap::{Arg, App, SubCommand, ArgMatches};
 
 
+/// Runs a DNS-01 challenge end to end: publishes the TXT record, waits for it to
+/// propagate, then tells the server to validate. `clear_txt_record` always runs once the
+/// record has been published, even if propagation or validation fails, so a failed
+/// attempt doesn't leave a stale `_acme-challenge` TXT record behind for the DNS provider
+/// to clean up by hand.
+fn complete_dns01_challenge(
+    dns_provider: &(DnsProvider + Send + Sync),
+    fqdn: &str,
+    value: &str,
+    challenge: &Challenge,
+    account: &Account,
+) -> Result<()> {
+    dns_provider.set_txt_record(fqdn, value)?;
+    let validated = acme_client::dns::wait_for_propagation(fqdn, value, Duration::from_secs(300), Duration::from_secs(10))
+        .and_then(|_| challenge.validate(account));
+    let cleared = dns_provider.clear_txt_record(fqdn, value);
+    validated?;
+    cleared
+}
+
 fn main() {
     let matches = App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
@@ -86,6 +109,27 @@ fn main() {
                         to generate a TXT record for domain")
                  .short("d")
                  .long("dns")
+                 .takes_value(false))
+            .arg(Arg::with_name("DNS_PROVIDER")
+                 .help("DNS-01 provider to publish the challenge record automatically. \
+                        Defaults to \"manual\", which prints the record and waits for \
+                        you to create it yourself. Supported values: manual, cloudflare.")
+                 .long("dns-provider")
+                 .takes_value(true))
+            .arg(Arg::with_name("DNS_API_TOKEN")
+                 .help("API token for --dns-provider.")
+                 .long("dns-api-token")
+                 .takes_value(true))
+            .arg(Arg::with_name("DNS_ZONE_ID")
+                 .help("Zone id for --dns-provider.")
+                 .long("dns-zone-id")
+                 .takes_value(true))
+            .arg(Arg::with_name("STANDALONE")
+                 .help("Answer the HTTP challenge with a built-in server on port 80 \
+                        instead of writing into --public-dir. Requires no existing \
+                        web server.")
+                 .long("standalone")
+                 .conflicts_with("PUBLIC_DIR")
                  .takes_value(false)))
         .subcommand(SubCommand::with_name("revoke")
             .about("Revokes a signed certificate")
@@ -102,6 +146,90 @@ fn main() {
                 .short("C")
                 .required(true)
                 .takes_value(true)))
+        .subcommand(SubCommand::with_name("renew")
+            .about("Runs a renewal daemon that keeps managed domains' certificates up to date")
+            .display_order(3)
+            .arg(Arg::with_name("USER_KEY_PATH")
+                .help("User private key path to use it in account registration.")
+                .long("user-key")
+                .short("U")
+                .takes_value(true))
+            .arg(Arg::with_name("DOMAIN")
+                .help("Domain name to manage. You can use more than one domain name.")
+                .short("D")
+                .long("domain")
+                .multiple(true)
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("PUBLIC_DIR")
+                .help("Directory to save ACME simple http challenge. This option is required.")
+                .short("P")
+                .long("public-dir")
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("STORE_DIR")
+                .help("Directory to keep managed certificates, keys and renewal state in.")
+                .short("o")
+                .long("store-dir")
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("RENEW_BEFORE_DAYS")
+                .help("Reissue a certificate once it has fewer than this many days left. \
+                       Defaults to 30.")
+                .long("renew-before-days")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("on-demand")
+            .about("Lazily issues a wildcard certificate the first time a hostname matching \
+                    an --on-demand-pattern is seen")
+            .display_order(4)
+            .arg(Arg::with_name("USER_KEY_PATH")
+                .help("User private key path to use it in account registration.")
+                .long("user-key")
+                .short("U")
+                .takes_value(true))
+            .arg(Arg::with_name("HOST")
+                .help("The concrete hostname that was just seen, e.g. by a reverse proxy.")
+                .short("H")
+                .long("host")
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("ON_DEMAND_PATTERN")
+                .help("Glob pattern allowed for on-demand issuance, e.g. \"*.example.org\". \
+                       Patterns that aren't themselves a valid wildcard identifier can supply \
+                       a fallback base domain as \"pattern=base\", e.g. \"*=example.org\".")
+                .short("O")
+                .long("on-demand-pattern")
+                .multiple(true)
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("STORE_DIR")
+                .help("Directory to keep managed certificates, keys and renewal state in.")
+                .short("o")
+                .long("store-dir")
+                .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("DNS_PROVIDER")
+                .help("DNS-01 provider to publish the challenge record automatically. \
+                       Defaults to \"manual\". Supported values: manual, cloudflare.")
+                .long("dns-provider")
+                .takes_value(true))
+            .arg(Arg::with_name("DNS_API_TOKEN")
+                .help("API token for --dns-provider.")
+                .long("dns-api-token")
+                .takes_value(true))
+            .arg(Arg::with_name("DNS_ZONE_ID")
+                .help("Zone id for --dns-provider.")
+                .long("dns-zone-id")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("check")
+            .about("Checks whether a signed certificate has been revoked")
+            .display_order(5)
+            .arg(Arg::with_name("SIGNED_CRT")
+                .help("Path to the signed certificate to check.")
+                .long("signed-crt")
+                .short("C")
+                .required(true)
+                .takes_value(true)))
         .arg(Arg::with_name("verbose")
              .help("Show verbose output")
              .short("v")
@@ -114,6 +242,12 @@ fn main() {
         sign_certificate(matches)
     } else if let Some(matches) = matches.subcommand_matches("revoke") {
         revoke_certificate(matches)
+    } else if let Some(matches) = matches.subcommand_matches("renew") {
+        renew_certificates(matches)
+    } else if let Some(matches) = matches.subcommand_matches("on-demand") {
+        ensure_on_demand_certificate(matches)
+    } else if let Some(matches) = matches.subcommand_matches("check") {
+        check_certificate(matches)
     } else {
         println!("{}", matches.usage());
         Ok(())
@@ -143,6 +277,13 @@ fn sign_certificate(matches: &ArgMatches) -> Result<()> {
                    or from --csr".into());
     }
 
+    // ACME only allows wildcard identifiers to be validated via DNS-01 (RFC 8555 8.3),
+    // so reject them up front instead of failing later with an obscure server error.
+    if domains.iter().any(|d| d.starts_with("*.")) && !matches.is_present("DNS_CHALLENGE") {
+        return Err("wildcard domains (*.example.org) can only be validated with the DNS \
+                    challenge, pass --dns".into());
+    }
+
     let directory = Directory::lets_encrypt()?;
 
     let mut account_registration = directory.account_registration();
@@ -157,30 +298,52 @@ fn sign_certificate(matches: &ArgMatches) -> Result<()> {
 
     let account = account_registration.register()?;
 
-    for domain in &domains {
-        let authorization = account.authorization(domain)?;
+    let dv: Vec<&str> = domains.iter().map(String::as_str).collect();
+    let mut order = directory.new_order(&account, &dv)?;
+
+    let standalone = if matches.is_present("STANDALONE") {
+        Some(StandaloneServer::start("0.0.0.0:80")?)
+    } else {
+        None
+    };
+
+    for mut authorization in order.authorizations(&account)? {
+        if authorization.is_wildcard() && !matches.is_present("DNS_CHALLENGE") {
+            return Err(format!(
+                "authorization for {} is a wildcard and requires --dns",
+                authorization.identifier()
+            ).into());
+        }
+
         if !matches.is_present("DNS_CHALLENGE") {
             let challenge = authorization.get_http_challenge().ok_or("HTTP challenge not found")?;
-            challenge.save_key_authorization(matches.value_of("PUBLIC_DIR")
-                                                 .ok_or("--public-dir not defined. \
-                                                            You need to define a public \
-                                                            directory to use http challenge \
-                                                            verification")?)?;
-            challenge.validate()?;
+            if let Some(ref server) = standalone {
+                server.register(challenge.token(), &challenge.key_authorization(&account)?);
+            } else {
+                save_key_authorization(matches.value_of("PUBLIC_DIR")
+                                           .ok_or("--public-dir not defined. \
+                                                      You need to define a public \
+                                                      directory to use http challenge \
+                                                      verification")?,
+                                       challenge,
+                                       &account)?;
+            }
+            challenge.validate(&account)?;
+            if let Some(ref server) = standalone {
+                server.unregister(challenge.token());
+            }
         } else {
             let challenge = authorization.get_dns_challenge().ok_or("DNS challenge not found")?;
-            println!("Please create a TXT record for _acme-challenge.{}: {}\n\
-                      Press enter to continue",
-                     domain,
-                     challenge.signature()?);
-            io::stdin().read_line(&mut String::new()).unwrap();
-            challenge.validate()?;
+            let fqdn = format!("_acme-challenge.{}", authorization.identifier());
+            let value = challenge.dns_txt_value(&account)?;
+
+            let dns_provider = dns_provider_from_args(matches)?;
+            complete_dns01_challenge(&*dns_provider, &fqdn, &value, challenge, &account)?;
         }
+        authorization.wait_done(&account, Duration::from_secs(2), 10)?;
     }
 
-    let dv: Vec<&str> = domains.iter().map(String::as_str).collect();
-    let mut certificate_signer = account.certificate_signer(dv.as_slice());
-
+    let mut certificate_signer = CertificateSigner::new();
     if let Some(domain_key_path) = matches.value_of("DOMAIN_KEY_PATH") {
         if let Some(csr_path) = matches.value_of("DOMAIN_CSR") {
             certificate_signer = certificate_signer.csr_from_file(domain_key_path, csr_path)?;
@@ -188,8 +351,14 @@ fn sign_certificate(matches: &ArgMatches) -> Result<()> {
             certificate_signer = certificate_signer.pkey_from_file(domain_key_path)?;
         }
     }
+    let csr_der = certificate_signer.generate_csr(&dv)?;
+
+    order.finalize(&account, &csr_der)?;
+    order.wait_done(&account, Duration::from_secs(2), 10)?;
+    let pem_chain = order.download_certificate(&account)?;
+    let (domain_pkey, domain_csr) = certificate_signer.into_parts();
+    let certificate = Certificate::new(domain_pkey, domain_csr, pem_chain);
 
-    let certificate = certificate_signer.sign_certificate()?;
     let signed_certificate_path = matches.value_of("SAVE_SIGNED_CERTIFICATE")
         .ok_or("You need to save signed certificate")?;
     if matches.is_present("CHAIN") {
@@ -211,6 +380,33 @@ fn sign_certificate(matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Builds the DNS-01 provider selected by `--dns-provider`, defaulting to the manual
+/// print-and-wait-for-enter behavior.
+fn dns_provider_from_args(matches: &ArgMatches) -> Result<Box<DnsProvider + Send + Sync>> {
+    match matches.value_of("DNS_PROVIDER").unwrap_or("manual") {
+        "manual" => Ok(Box::new(ManualDnsProvider)),
+        "cloudflare" => {
+            let api_token = matches.value_of("DNS_API_TOKEN")
+                .ok_or("--dns-api-token is required for --dns-provider cloudflare")?;
+            let zone_id = matches.value_of("DNS_ZONE_ID")
+                .ok_or("--dns-zone-id is required for --dns-provider cloudflare")?;
+            Ok(Box::new(CloudflareDnsProvider::new(api_token, zone_id)))
+        }
+        other => Err(format!("unknown --dns-provider \"{}\"", other).into()),
+    }
+}
+
+/// Writes a challenge's key authorization under `PUBLIC_DIR/.well-known/acme-challenge/<token>`,
+/// the path the HTTP-01 validator expects to fetch.
+fn save_key_authorization(public_dir: &str, challenge: &Challenge, account: &Account) -> Result<()> {
+    use std::fs;
+    let key_authorization = challenge.key_authorization(account)?;
+    let dir = Path::new(public_dir).join(".well-known").join("acme-challenge");
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(challenge.token()), key_authorization)?;
+    Ok(())
+}
+
 
 fn revoke_certificate(matches: &ArgMatches) -> Result<()> {
     let directory = Directory::lets_encrypt()?;
@@ -228,6 +424,142 @@ fn revoke_certificate(matches: &ArgMatches) -> Result<()> {
 }
 
 
+/// Runs a `CertStore`-backed renewal daemon: registers a single account up front, then
+/// keeps every `--domain` renewed via HTTP-01 challenges served from `--public-dir`,
+/// checking once an hour for as long as the process runs.
+fn renew_certificates(matches: &ArgMatches) -> Result<()> {
+    let directory = Directory::lets_encrypt()?;
+
+    let mut account_registration = directory.account_registration();
+    if let Some(user_key_path) = matches.value_of("USER_KEY_PATH") {
+        account_registration = account_registration.pkey_from_file(user_key_path)?;
+    }
+    let account = account_registration.register()?;
+
+    let public_dir = matches.value_of("PUBLIC_DIR")
+        .ok_or("--public-dir not defined. You need to define a public directory to use \
+                http challenge verification")?
+        .to_owned();
+    let store_dir = matches.value_of("STORE_DIR").ok_or("--store-dir not defined")?;
+    let renew_before_days: u64 = matches.value_of("RENEW_BEFORE_DAYS")
+        .map(|v| v.parse().unwrap_or(30))
+        .unwrap_or(30);
+
+    let issuer = build_http_issuer(directory, account, public_dir);
+
+    let mut store = acme_client::CertStore::new(store_dir, issuer)?
+        .renew_before(Duration::from_secs(renew_before_days * 24 * 60 * 60));
+    for domain in matches.values_of("DOMAIN").ok_or("You need to provide at least one domain name")? {
+        // build_http_issuer only ever requests an http-01 challenge, which ACME won't
+        // offer for a wildcard identifier (RFC 8555 8.3); reject it here instead of
+        // failing deep inside the renewal loop with an opaque "HTTP challenge not found".
+        if domain.starts_with("*.") {
+            return Err("wildcard domains (*.example.org) can't be renewed over HTTP-01; use \
+                        `on-demand` with a --dns-provider instead".into());
+        }
+        store.add_domain(domain);
+    }
+
+    store.run(Duration::from_secs(60 * 60))
+}
+
+/// Builds a `CertStore` issuer that satisfies a single `http-01` challenge per domain by
+/// writing into `public_dir`, the same thing `sign --public-dir` does.
+fn build_http_issuer(directory: Directory, account: Account, public_dir: String) -> acme_client::cert_store::Issuer {
+    Box::new(move |domain: &str| {
+        let order_domains = [domain];
+        let mut order = directory.new_order(&account, &order_domains)?;
+        for mut authorization in order.authorizations(&account)? {
+            let challenge = authorization.get_http_challenge().ok_or("HTTP challenge not found")?;
+            save_key_authorization(&public_dir, challenge, &account)?;
+            challenge.validate(&account)?;
+            authorization.wait_done(&account, Duration::from_secs(2), 10)?;
+        }
+        finalize_single_domain_order(order, &account, &order_domains)
+    })
+}
+
+/// Builds a `CertStore` issuer that satisfies a single `dns-01` challenge per domain
+/// through `dns_provider`. This is the only challenge type ACME allows for wildcard
+/// identifiers, so it's what on-demand wildcard issuance uses.
+fn build_dns_issuer(directory: Directory, account: Account, dns_provider: Box<DnsProvider + Send + Sync>) -> acme_client::cert_store::Issuer {
+    Box::new(move |domain: &str| {
+        let order_domains = [domain];
+        let mut order = directory.new_order(&account, &order_domains)?;
+        for mut authorization in order.authorizations(&account)? {
+            let challenge = authorization.get_dns_challenge().ok_or("DNS challenge not found")?;
+            let fqdn = format!("_acme-challenge.{}", authorization.identifier());
+            let value = challenge.dns_txt_value(&account)?;
+            complete_dns01_challenge(&*dns_provider, &fqdn, &value, challenge, &account)?;
+            authorization.wait_done(&account, Duration::from_secs(2), 10)?;
+        }
+        finalize_single_domain_order(order, &account, &order_domains)
+    })
+}
+
+fn finalize_single_domain_order(mut order: acme_client::Order, account: &Account, order_domains: &[&str]) -> Result<(Vec<u8>, String)> {
+    let mut certificate_signer = CertificateSigner::new();
+    let csr_der = certificate_signer.generate_csr(order_domains)?;
+    order.finalize(account, &csr_der)?;
+    order.wait_done(account, Duration::from_secs(2), 10)?;
+    let cert_pem = order.download_certificate(account)?;
+
+    let (pkey, _) = certificate_signer.into_parts();
+    let key_pem = pkey.ok_or("CSR generation did not produce a private key")?.private_key_to_pem()?;
+    Ok((key_pem, cert_pem))
+}
+
+/// Lazily issues (or reuses) a wildcard certificate the first time a hostname matching an
+/// `--on-demand-pattern` is seen, e.g. invoked from a reverse proxy's auth hook.
+fn ensure_on_demand_certificate(matches: &ArgMatches) -> Result<()> {
+    let directory = Directory::lets_encrypt()?;
+
+    let mut account_registration = directory.account_registration();
+    if let Some(user_key_path) = matches.value_of("USER_KEY_PATH") {
+        account_registration = account_registration.pkey_from_file(user_key_path)?;
+    }
+    let account = account_registration.register()?;
+
+    let store_dir = matches.value_of("STORE_DIR").ok_or("--store-dir not defined")?;
+    let host = matches.value_of("HOST").ok_or("--host not defined")?;
+
+    let mut on_demand = OnDemandDomains::new();
+    for pattern in matches.values_of("ON_DEMAND_PATTERN").ok_or("--on-demand-pattern not defined")? {
+        let mut parts = pattern.splitn(2, '=');
+        let glob = parts.next().unwrap();
+        let fallback = parts.next();
+        on_demand.add(glob, fallback)?;
+    }
+
+    let dns_provider = dns_provider_from_args(matches)?;
+    let issuer = build_dns_issuer(directory, account, dns_provider);
+    let mut store = acme_client::CertStore::new(store_dir, issuer)?;
+    store.ensure_on_demand(host, &on_demand)?;
+    store.check_all()
+}
+
+
+/// Checks a signed certificate against its own CRL distribution points and prints the
+/// result, exiting non-zero if it's revoked so this is usable from a monitoring script.
+fn check_certificate(matches: &ArgMatches) -> Result<()> {
+    let path = matches.value_of("SIGNED_CRT").ok_or("You need to provide a signed certificate to check.")?;
+    match revocation::check_file(path)? {
+        RevocationStatus::Good => {
+            println!("good");
+            Ok(())
+        }
+        RevocationStatus::Unknown => {
+            println!("unknown");
+            Ok(())
+        }
+        RevocationStatus::Revoked => {
+            println!("revoked");
+            Err("certificate has been revoked".into())
+        }
+    }
+}
+
+
 fn init_logger(level: u64) {
     let level = match level {
         0 => "",
@@ -0,0 +1,115 @@
+//! On-demand issuance: a whitelist of glob patterns that triggers lazy wildcard issuance
+//! the first time a matching hostname is seen, rather than requiring every domain to be
+//! configured up front. Mirrors tricot's `on_demand_domains: Vec<(glob::Pattern, Option<String>)>`.
+
+use glob::Pattern;
+use error::Result;
+
+/// A set of glob patterns allowed to be issued on demand. Each pattern optionally carries
+/// a fallback base domain, used when the pattern itself (e.g. a bare `*`) isn't a valid
+/// ACME wildcard identifier on its own.
+pub struct OnDemandDomains {
+    patterns: Vec<(Pattern, Option<String>)>,
+}
+
+impl OnDemandDomains {
+    pub fn new() -> OnDemandDomains {
+        OnDemandDomains { patterns: Vec::new() }
+    }
+
+    /// Registers a glob pattern (e.g. `*.example.org`) to allow on-demand issuance for,
+    /// with an optional fallback base domain for patterns that aren't themselves a valid
+    /// wildcard identifier.
+    pub fn add(&mut self, pattern: &str, fallback_base_domain: Option<&str>) -> Result<()> {
+        let pattern = Pattern::new(pattern).map_err(|e| format!("invalid glob pattern: {}", e))?;
+        self.patterns.push((pattern, fallback_base_domain.map(str::to_owned)));
+        Ok(())
+    }
+
+    /// Finds the first registered pattern `hostname` matches and returns the ACME
+    /// wildcard identifier to request a certificate for (e.g. `*.example.org`), which
+    /// doubles as the cache key every subsequent hostname matching that same pattern
+    /// reuses, since a single wildcard cert already covers them all.
+    pub fn wildcard_for(&self, hostname: &str) -> Option<Result<String>> {
+        for &(ref pattern, ref fallback) in &self.patterns {
+            if !matches_single_label_wildcard(pattern, hostname) {
+                continue;
+            }
+            let pattern_str = pattern.as_str();
+            if pattern_str.starts_with("*.") {
+                return Some(Ok(pattern_str.to_owned()));
+            }
+            return Some(match *fallback {
+                Some(ref base) => Ok(format!("*.{}", base)),
+                None => Err(format!(
+                    "on-demand pattern \"{}\" is not a valid wildcard identifier and has no fallback base domain",
+                    pattern_str
+                ).into()),
+            });
+        }
+        None
+    }
+}
+
+/// Whether `hostname` matches `pattern`, treating a `*.`-prefixed pattern per RFC 6125
+/// 6.4.3: the wildcard stands in for exactly one DNS label, not `glob::Pattern`'s normal
+/// behavior of letting `*` span any number of `.`-separated segments. Without this,
+/// `*.example.org` would also match `a.b.example.org`, which is not a certificate X.509
+/// would ever consider that pattern's wildcard to cover.
+fn matches_single_label_wildcard(pattern: &Pattern, hostname: &str) -> bool {
+    let pattern_str = pattern.as_str();
+    if pattern_str.starts_with("*.") {
+        let suffix = &pattern_str[2..];
+        return match hostname.find('.') {
+            Some(idx) => !hostname[..idx].is_empty() && &hostname[idx + 1..] == suffix,
+            None => false,
+        };
+    }
+    pattern.matches(hostname)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OnDemandDomains;
+
+    #[test]
+    fn wildcard_pattern_matches_directly() {
+        let mut on_demand = OnDemandDomains::new();
+        on_demand.add("*.example.org", None).unwrap();
+        assert_eq!(on_demand.wildcard_for("www.example.org").unwrap().unwrap(), "*.example.org");
+    }
+
+    #[test]
+    fn non_wildcard_pattern_uses_fallback_base_domain() {
+        let mut on_demand = OnDemandDomains::new();
+        on_demand.add("*", Some("example.org")).unwrap();
+        assert_eq!(on_demand.wildcard_for("anything").unwrap().unwrap(), "*.example.org");
+    }
+
+    #[test]
+    fn non_wildcard_pattern_without_fallback_errors() {
+        let mut on_demand = OnDemandDomains::new();
+        on_demand.add("*", None).unwrap();
+        assert!(on_demand.wildcard_for("anything").unwrap().is_err());
+    }
+
+    #[test]
+    fn non_matching_host_returns_none() {
+        let mut on_demand = OnDemandDomains::new();
+        on_demand.add("*.example.org", None).unwrap();
+        assert!(on_demand.wildcard_for("evil.example").is_none());
+    }
+
+    #[test]
+    fn wildcard_pattern_does_not_span_multiple_labels() {
+        let mut on_demand = OnDemandDomains::new();
+        on_demand.add("*.example.org", None).unwrap();
+        assert!(on_demand.wildcard_for("a.b.example.org").is_none());
+    }
+
+    #[test]
+    fn invalid_glob_pattern_errors() {
+        let mut on_demand = OnDemandDomains::new();
+        assert!(on_demand.add("[", None).is_err());
+    }
+}
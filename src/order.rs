@@ -0,0 +1,107 @@
+//! ACME v2 orders (RFC 8555 section 7.4): the object that ties a set of identifiers to
+//! their authorizations and, once validated, to a finalized certificate.
+
+use std::thread::sleep;
+use std::time::Duration;
+use serde_json::Value;
+use account::Account;
+use authorization::Authorization;
+use http::Response;
+use error::{Error, Result};
+
+/// An in-progress or completed ACME order.
+pub struct Order {
+    url: String,
+    status: String,
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+impl Order {
+    pub(crate) fn from_response(url: String, res: &Response) -> Result<Order> {
+        let json: Value = ::serde_json::from_str(&res.body).map_err(|e| Error::Json(e.to_string()))?;
+        Order::from_json(url, &json)
+    }
+
+    fn from_json(url: String, json: &Value) -> Result<Order> {
+        let status = json.get("status")
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::Json("order is missing \"status\"".into()))?
+            .to_owned();
+        let authorizations = json.get("authorizations")
+            .and_then(Value::as_array)
+            .ok_or_else(|| Error::Json("order is missing \"authorizations\"".into()))?
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect();
+        let finalize = json.get("finalize")
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::Json("order is missing \"finalize\"".into()))?
+            .to_owned();
+        let certificate = json.get("certificate").and_then(Value::as_str).map(str::to_owned);
+
+        Ok(Order {
+            url: url,
+            status: status,
+            authorizations: authorizations,
+            finalize: finalize,
+            certificate: certificate,
+        })
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.status == "valid"
+    }
+
+    /// POST-as-GETs every authorization URL in this order.
+    pub fn authorizations(&self, account: &Account) -> Result<Vec<Authorization>> {
+        self.authorizations
+            .iter()
+            .map(|url| Authorization::fetch(account, url))
+            .collect()
+    }
+
+    /// POSTs the DER-encoded CSR to the order's `finalize` URL. The order should only be
+    /// finalized once every authorization it covers is `valid`.
+    pub fn finalize(&mut self, account: &Account, csr_der: &[u8]) -> Result<()> {
+        let payload = format!("{{\"csr\":\"{}\"}}", ::helper::b64(csr_der));
+        let res = account.request(&self.finalize, &payload)?;
+        *self = Order::from_json(self.url.clone(), &::serde_json::from_str(&res.body).map_err(|e| Error::Json(e.to_string()))?)?;
+        Ok(())
+    }
+
+    /// Polls the order URL (POST-as-GET) until its status is no longer `pending` or `processing`,
+    /// sleeping `interval` between attempts, up to `attempts` times. An `invalid` status
+    /// is a terminal failure (RFC 8555 section 7.1.6) -- it will never become `valid` on
+    /// its own, so this returns an error immediately instead of letting the caller find
+    /// out the hard way when `download_certificate` has no certificate URL to fetch.
+    pub fn wait_done(&mut self, account: &Account, interval: Duration, attempts: u32) -> Result<()> {
+        for _ in 0..attempts {
+            if self.status == "invalid" {
+                return Err(Error::Acme("order failed, status is \"invalid\"".into()));
+            }
+            if self.status != "processing" && self.status != "pending" {
+                return Ok(());
+            }
+            sleep(interval);
+            let res = account.request_as_get(&self.url)?;
+            *self = Order::from_response(self.url.clone(), &res)?;
+        }
+        Err(Error::Acme(format!("order did not finish, last status was \"{}\"", self.status)))
+    }
+
+    /// POST-as-GETs the `certificate` URL and returns the full PEM chain. Only valid once
+    /// the order's status is `valid`.
+    pub fn download_certificate(&self, account: &Account) -> Result<String> {
+        let url = self.certificate.as_ref()
+            .ok_or("order has no certificate URL yet, finalize and wait for it to become valid first")?;
+        let res = account.request_as_get(url)?;
+        Ok(res.body)
+    }
+}
@@ -0,0 +1,113 @@
+//! A rustls `ResolvesServerCert` adapter backed by a managed certificate set, so a
+//! hyper/tokio TLS listener can serve freshly issued or renewed certificates without a
+//! restart or a file reload.
+//!
+//! This module is only compiled with the `rustls` feature enabled.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use rustls::{ResolvesServerCert, ClientHello, sign, Certificate as RustlsCertificate, PrivateKey};
+use cert_store::RenewalObserver;
+use error::{Error, Result};
+
+/// Resolves a TLS server certificate by SNI name against a set of certificates kept in
+/// memory, picking up renewals through interior mutability so rotation is invisible to
+/// already-connected clients.
+pub struct CertResolver {
+    certs: RwLock<HashMap<String, Arc<sign::CertifiedKey>>>,
+}
+
+impl CertResolver {
+    pub fn new() -> Arc<CertResolver> {
+        Arc::new(CertResolver { certs: RwLock::new(HashMap::new()) })
+    }
+
+    /// Registers (or replaces) the certificate served for `host`.
+    pub fn insert(&self, host: &str, key: sign::CertifiedKey) {
+        self.certs.write().unwrap().insert(host.to_owned(), Arc::new(key));
+    }
+
+    /// Parses a PEM key/chain pair and registers them for `host`.
+    pub fn insert_pem(&self, host: &str, key_pem: &[u8], cert_pem_chain: &str) -> Result<()> {
+        let key = rustls_pemfile_pkey(key_pem)?;
+        let signing_key = sign::RSASigningKey::new(&key)
+            .map_err(|_| Error::Other("unsupported private key for TLS".into()))?;
+        let chain: Vec<RustlsCertificate> = pem_to_der_chain(cert_pem_chain)
+            .into_iter()
+            .map(RustlsCertificate)
+            .collect();
+        self.insert(host, sign::CertifiedKey::new(chain, Arc::new(Box::new(signing_key))));
+        Ok(())
+    }
+
+    /// Longest-suffix match against the registered hosts, so `www.example.org` matches a
+    /// registration for `example.org` and `*.example.org` matches any single label prefix.
+    fn find(&self, name: &str) -> Option<Arc<sign::CertifiedKey>> {
+        let certs = self.certs.read().unwrap();
+        if let Some(key) = certs.get(name) {
+            return Some(key.clone());
+        }
+        if let Some(key) = wildcard_key(name).and_then(|wildcard| certs.get(&wildcard).cloned()) {
+            return Some(key);
+        }
+        let mut suffix = name;
+        while let Some(idx) = suffix.find('.') {
+            suffix = &suffix[idx + 1..];
+            if let Some(key) = certs.get(suffix) {
+                return Some(key.clone());
+            }
+        }
+        None
+    }
+}
+
+/// The wildcard registration key (`*.example.org`) that would cover `name`
+/// (`www.example.org`), or `None` if `name` has no parent domain to wildcard against.
+fn wildcard_key(name: &str) -> Option<String> {
+    name.find('.').map(|idx| format!("*{}", &name[idx..]))
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<sign::CertifiedKey> {
+        let name = client_hello.server_name()?;
+        self.find(name.as_ref()).map(|key| (*key).clone())
+    }
+}
+
+impl RenewalObserver for CertResolver {
+    fn on_renewed(&self, domain: &str, key_pem: &[u8], cert_pem_chain: &str) {
+        if let Err(err) = self.insert_pem(domain, key_pem, cert_pem_chain) {
+            error!("failed to load renewed certificate for {} into resolver: {}", domain, err);
+        }
+    }
+}
+
+fn rustls_pemfile_pkey(key_pem: &[u8]) -> Result<PrivateKey> {
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    let der = PKey::from_rsa(Rsa::private_key_from_pem(key_pem)?)?.private_key_to_der()?;
+    Ok(PrivateKey(der))
+}
+
+fn pem_to_der_chain(pem_chain: &str) -> Vec<Vec<u8>> {
+    use openssl::x509::X509;
+    X509::stack_from_pem(pem_chain.as_bytes())
+        .map(|certs| certs.into_iter().filter_map(|c| c.to_der().ok()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wildcard_key;
+
+    #[test]
+    fn wildcard_key_derives_parent_domain() {
+        assert_eq!(wildcard_key("www.example.org"), Some("*.example.org".to_owned()));
+        assert_eq!(wildcard_key("a.b.example.org"), Some("*.b.example.org".to_owned()));
+    }
+
+    #[test]
+    fn wildcard_key_none_for_bare_domain() {
+        assert_eq!(wildcard_key("localhost"), None);
+    }
+}
@@ -0,0 +1,458 @@
+//! CRL-based revocation checking. Follows the same approach `names_from_csr` already
+//! uses for reading a SAN extension: drop into raw OpenSSL FFI for the handful of
+//! structures the safe `openssl` wrapper doesn't expose yet.
+
+use std::os::raw::{c_int, c_long, c_uchar, c_void};
+use std::ptr;
+use std::slice;
+use openssl::x509::X509;
+use openssl::types::OpenSslTypeRef;
+use openssl_sys;
+use http;
+use error::Result;
+
+/// Whether a certificate's serial number appears on its issuer's CRL, or (failing that)
+/// its issuer's OCSP responder reports it revoked.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RevocationStatus {
+    Good,
+    Revoked,
+    /// Neither a usable CRL Distribution Point nor an OCSP lookup could confirm a status.
+    Unknown,
+}
+
+#[repr(C)]
+struct Asn1StringSt {
+    length: c_int,
+    type_: c_int,
+    data: *mut c_uchar,
+    flags: c_long,
+}
+
+enum Asn1IntegerSt {}
+enum X509CrlSt {}
+enum OpensslStackSt {}
+enum EvpMdSt {}
+enum OcspCertIdSt {}
+enum OcspRequestSt {}
+enum OcspResponseSt {}
+enum OcspBasicRespSt {}
+
+extern "C" {
+    fn X509_get_ext_by_NID(x: *mut openssl_sys::X509, nid: c_int, lastpos: c_int) -> c_int;
+    fn X509_get_ext(x: *mut openssl_sys::X509, loc: c_int) -> *mut openssl_sys::X509_EXTENSION;
+    fn X509_EXTENSION_get_data(ne: *mut openssl_sys::X509_EXTENSION) -> *mut Asn1StringSt;
+    fn d2i_X509_CRL(
+        crl: *mut *mut X509CrlSt,
+        data: *mut *const c_uchar,
+        len: c_long,
+    ) -> *mut X509CrlSt;
+    fn X509_CRL_get_REVOKED(crl: *mut X509CrlSt) -> *mut OpensslStackSt;
+    fn X509_CRL_get_ext_by_NID(crl: *mut X509CrlSt, nid: c_int, lastpos: c_int) -> c_int;
+    fn X509_CRL_get_ext(crl: *mut X509CrlSt, loc: c_int) -> *mut openssl_sys::X509_EXTENSION;
+    fn X509_CRL_free(crl: *mut X509CrlSt);
+    fn X509_REVOKED_get0_serialNumber(rev: *const c_void) -> *mut Asn1IntegerSt;
+    fn ASN1_STRING_length(asn1: *const Asn1IntegerSt) -> c_int;
+    fn ASN1_STRING_get0_data(asn1: *const Asn1IntegerSt) -> *const c_uchar;
+    fn OPENSSL_sk_num(stack: *const OpensslStackSt) -> c_int;
+    fn OPENSSL_sk_value(stack: *const OpensslStackSt, idx: c_int) -> *mut c_void;
+
+    fn EVP_sha1() -> *const EvpMdSt;
+    fn OCSP_cert_to_id(
+        dgst: *const EvpMdSt,
+        subject: *mut openssl_sys::X509,
+        issuer: *mut openssl_sys::X509,
+    ) -> *mut OcspCertIdSt;
+    fn OCSP_CERTID_free(cid: *mut OcspCertIdSt);
+    fn OCSP_REQUEST_new() -> *mut OcspRequestSt;
+    fn OCSP_REQUEST_free(req: *mut OcspRequestSt);
+    fn OCSP_request_add0_id(req: *mut OcspRequestSt, cid: *mut OcspCertIdSt) -> *mut c_void;
+    fn i2d_OCSP_REQUEST(req: *mut OcspRequestSt, out: *mut *mut c_uchar) -> c_int;
+    fn d2i_OCSP_RESPONSE(
+        resp: *mut *mut OcspResponseSt,
+        data: *mut *const c_uchar,
+        len: c_long,
+    ) -> *mut OcspResponseSt;
+    fn OCSP_RESPONSE_free(resp: *mut OcspResponseSt);
+    fn OCSP_response_status(resp: *mut OcspResponseSt) -> c_int;
+    fn OCSP_response_get1_basic(resp: *mut OcspResponseSt) -> *mut OcspBasicRespSt;
+    fn OCSP_BASICRESP_free(bs: *mut OcspBasicRespSt);
+    fn OCSP_resp_find_status(
+        bs: *mut OcspBasicRespSt,
+        cid: *mut OcspCertIdSt,
+        status: *mut c_int,
+        reason: *mut c_int,
+        revtime: *mut *mut c_void,
+        thisupd: *mut *mut c_void,
+        nextupd: *mut *mut c_void,
+    ) -> c_int;
+    fn OPENSSL_free(ptr: *mut c_void);
+}
+
+/// `OCSP_response_status` value meaning the responder actually answered (as opposed to
+/// e.g. `malformed_request` or `try_later`).
+const OCSP_RESPONSE_STATUS_SUCCESSFUL: c_int = 0;
+
+/// `OCSP_resp_find_status` cert status values (`openssl/ocsp.h`).
+const V_OCSP_CERTSTATUS_GOOD: c_int = 0;
+const V_OCSP_CERTSTATUS_REVOKED: c_int = 1;
+
+/// Reads the raw DER payload of the extension identified by `nid`. Returns `None` if the
+/// certificate doesn't carry that extension at all, which is OpenSSL's normal
+/// `asn1_NOVALUE` convention for an absent optional field -- a perfectly ordinary
+/// certificate, not something to error on.
+fn extension_data(cert: &X509, nid: c_int) -> Option<Vec<u8>> {
+    unsafe {
+        let idx = X509_get_ext_by_NID(cert.as_ptr(), nid, -1);
+        if idx < 0 {
+            return None;
+        }
+        let ext = X509_get_ext(cert.as_ptr(), idx);
+        if ext.is_null() {
+            return None;
+        }
+        let data = X509_EXTENSION_get_data(ext);
+        if data.is_null() || (*data).data.is_null() {
+            return None;
+        }
+        Some(slice::from_raw_parts((*data).data, (*data).length as usize).to_vec())
+    }
+}
+
+/// Pulls every `[6] URI` GeneralName (DER context tag `0x86`) out of a DER blob. Both the
+/// CRL Distribution Points and Authority Information Access extensions encode their URLs
+/// this way, so one scanner covers both -- the same "split on the GeneralName tag byte"
+/// approach `parse_asn1_octet_str` already uses for SAN `dNSName` entries (tag `0x82`), so
+/// this avoids re-deriving (and potentially double-decoding) that altNames logic.
+fn uris_from_der(der: &[u8]) -> Vec<String> {
+    const URI_TAG: u8 = 0x86;
+    let mut uris = Vec::new();
+    let mut i = 0;
+    while i + 1 < der.len() {
+        if der[i] == URI_TAG {
+            // DER length: short-form is a single byte with the high bit clear; long-form
+            // sets the high bit and uses the low 7 bits as a count of following
+            // big-endian length bytes. CRL/AIA URIs are short in practice but nothing
+            // stops a CA from using long-form, so both have to be handled here.
+            let first = der[i + 1] as usize;
+            let (len, header_len) = if first & 0x80 == 0 {
+                (first, 2)
+            } else {
+                let num_bytes = first & 0x7f;
+                if num_bytes == 0 || i + 2 + num_bytes > der.len() {
+                    i += 1;
+                    continue;
+                }
+                let mut len = 0usize;
+                for &b in &der[i + 2..i + 2 + num_bytes] {
+                    len = (len << 8) | b as usize;
+                }
+                (len, 2 + num_bytes)
+            };
+            if i + header_len + len <= der.len() {
+                uris.push(String::from_utf8_lossy(&der[i + header_len..i + header_len + len]).into_owned());
+                i += header_len + len;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    uris
+}
+
+/// Same as `extension_data`, but for an extension carried directly on a CRL (e.g. its
+/// Issuing Distribution Point) rather than on a certificate.
+fn crl_extension_data(crl: *mut X509CrlSt, nid: c_int) -> Option<Vec<u8>> {
+    unsafe {
+        let idx = X509_CRL_get_ext_by_NID(crl, nid, -1);
+        if idx < 0 {
+            return None;
+        }
+        let ext = X509_CRL_get_ext(crl, idx);
+        if ext.is_null() {
+            return None;
+        }
+        let data = X509_EXTENSION_get_data(ext);
+        if data.is_null() || (*data).data.is_null() {
+            return None;
+        }
+        Some(slice::from_raw_parts((*data).data, (*data).length as usize).to_vec())
+    }
+}
+
+/// The distribution point URI named in a CRL's own Issuing Distribution Point extension,
+/// if it has one. A v1 CRL has no extensions at all and so never has an IDP; even a v2
+/// CRL doesn't require one. Absent here just means "this CRL doesn't restrict its scope",
+/// not an error.
+fn idp_distribution_point_uri(crl: *mut X509CrlSt) -> Option<String> {
+    crl_extension_data(crl, openssl_sys::NID_issuing_distribution_point)
+        .map(|der| uris_from_der(&der))
+        .and_then(|mut uris| if uris.is_empty() { None } else { Some(uris.remove(0)) })
+}
+
+/// Every CRL Distribution Point URL advertised in `cert`'s extensions, or an empty vec if
+/// it doesn't have the extension.
+pub fn crl_distribution_points(cert: &X509) -> Vec<String> {
+    extension_data(cert, openssl_sys::NID_crl_distribution_points)
+        .map(|der| uris_from_der(&der))
+        .unwrap_or_default()
+}
+
+/// DER encoding of the `id-ad-ocsp` OID (1.3.6.1.5.5.7.48.1), as it appears inside an
+/// Authority Information Access `AccessDescription`.
+const OCSP_ACCESS_METHOD_OID: [u8; 8] = [0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01];
+
+/// DER encoding of the `id-ad-caIssuers` OID (1.3.6.1.5.5.7.48.2).
+const CA_ISSUERS_ACCESS_METHOD_OID: [u8; 8] = [0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x02];
+
+/// The URI of the first `AccessDescription` in an Authority Information Access extension
+/// whose `accessMethod` is `oid`. `AccessDescription ::= SEQUENCE { accessMethod OBJECT
+/// IDENTIFIER, accessLocation GeneralName }`, so the access method's OID bytes are always
+/// immediately followed by their own location's GeneralName -- scanning for the URI tag
+/// right after the OID match (rather than just taking the extension's first URI, which
+/// could belong to an entirely different access method) is what actually picks out the
+/// right one.
+fn access_location_uri(der: &[u8], oid: &[u8; 8]) -> Option<String> {
+    let pos = der.windows(oid.len()).position(|w| w == &oid[..])?;
+    uris_from_der(&der[pos + oid.len()..]).into_iter().next()
+}
+
+/// The OCSP responder URL advertised in `cert`'s Authority Information Access extension,
+/// if it has one.
+fn ocsp_responder_url(cert: &X509) -> Option<String> {
+    extension_data(cert, openssl_sys::NID_info_access)
+        .and_then(|der| access_location_uri(&der, &OCSP_ACCESS_METHOD_OID))
+}
+
+/// The CA Issuers URL advertised in `cert`'s Authority Information Access extension, used
+/// to fetch the issuing certificate an OCSP request needs to build a `CertID`.
+fn ca_issuer_url(cert: &X509) -> Option<String> {
+    extension_data(cert, openssl_sys::NID_info_access)
+        .and_then(|der| access_location_uri(&der, &CA_ISSUERS_ACCESS_METHOD_OID))
+}
+
+/// Downloads and parses the CRL at `url`, returning every revoked serial number as a
+/// big-endian byte string. A v1 CRL (no extensions at all, including no Issuing
+/// Distribution Point) and a v2 CRL without an IDP both parse the same way here. A v2 CRL
+/// that does carry an IDP naming a distribution point is only trusted if that name
+/// matches the URL we fetched it from -- the one case where the IDP field actually gets
+/// compared, and only because it was actually present.
+fn fetch_revoked_serials(url: &str) -> Result<Vec<Vec<u8>>> {
+    let der = http::get_bytes(url)?;
+
+    unsafe {
+        let mut ptr = der.as_ptr();
+        let crl = d2i_X509_CRL(::std::ptr::null_mut(), &mut ptr, der.len() as c_long);
+        if crl.is_null() {
+            return Err("could not parse CRL".into());
+        }
+
+        if let Some(idp_uri) = idp_distribution_point_uri(crl) {
+            if idp_uri != url {
+                X509_CRL_free(crl);
+                return Err(format!(
+                    "CRL at {} declares an Issuing Distribution Point of {}, refusing to trust it for this lookup",
+                    url,
+                    idp_uri
+                ).into());
+            }
+        }
+
+        // A v1 CRL (no extensions, e.g. no Issuing Distribution Point) still has a
+        // `revokedCertificates` list; `X509_CRL_get_REVOKED` returns null only when the
+        // list itself is absent, which is the one case we need to special-case here.
+        let revoked = X509_CRL_get_REVOKED(crl);
+        let mut serials = Vec::new();
+        if !revoked.is_null() {
+            for i in 0..OPENSSL_sk_num(revoked) {
+                let entry = OPENSSL_sk_value(revoked, i);
+                if entry.is_null() {
+                    continue;
+                }
+                let serial = X509_REVOKED_get0_serialNumber(entry);
+                if serial.is_null() {
+                    continue;
+                }
+                let data = ASN1_STRING_get0_data(serial);
+                let len = ASN1_STRING_length(serial);
+                if !data.is_null() && len > 0 {
+                    serials.push(slice::from_raw_parts(data, len as usize).to_vec());
+                }
+            }
+        }
+
+        X509_CRL_free(crl);
+        Ok(serials)
+    }
+}
+
+/// Strips a single leading `0x00` used only to keep a DER INTEGER's top bit from being
+/// read as a sign bit. `BIGNUM::to_vec()` (used for the certificate's own serial) never
+/// includes this byte, while the CRL entry's serial is read straight from its DER content
+/// octets and does, so both sides need to be compared in this normalized form.
+fn strip_der_sign_byte(serial: &[u8]) -> &[u8] {
+    if serial.len() > 1 && serial[0] == 0x00 {
+        &serial[1..]
+    } else {
+        serial
+    }
+}
+
+/// Asks `cert`'s OCSP responder directly whether it's been revoked, for the (common)
+/// case where it has no usable CRL Distribution Point. Building the request needs the
+/// issuing certificate too, fetched from the AIA CA Issuers URL -- an OCSP `CertID` is
+/// derived from the issuer's name and public key, not just the subject certificate.
+fn check_ocsp(cert: &X509) -> Result<RevocationStatus> {
+    let responder_url = ocsp_responder_url(cert)
+        .ok_or("certificate has no OCSP responder URL")?;
+    let issuer_url = ca_issuer_url(cert)
+        .ok_or("certificate has no CA Issuers URL to fetch the issuing certificate from")?;
+    let issuer = X509::from_der(&http::get_bytes(&issuer_url)?)?;
+
+    unsafe {
+        let dgst = EVP_sha1();
+
+        // The request's own CertID is owned (and freed) by the OCSP_REQUEST once handed
+        // over via `OCSP_request_add0_id`; the lookup below needs a second, independent
+        // one since the request is freed as soon as it's been DER-encoded.
+        let request_cid = OCSP_cert_to_id(dgst, cert.as_ptr(), issuer.as_ptr());
+        if request_cid.is_null() {
+            return Err("could not build OCSP certificate id".into());
+        }
+        let req = OCSP_REQUEST_new();
+        if req.is_null() {
+            OCSP_CERTID_free(request_cid);
+            return Err("could not build OCSP request".into());
+        }
+        OCSP_request_add0_id(req, request_cid);
+
+        let mut buf: *mut c_uchar = ptr::null_mut();
+        let len = i2d_OCSP_REQUEST(req, &mut buf);
+        if len <= 0 || buf.is_null() {
+            OCSP_REQUEST_free(req);
+            return Err("could not encode OCSP request".into());
+        }
+        let request_der = slice::from_raw_parts(buf, len as usize).to_vec();
+        OPENSSL_free(buf as *mut c_void);
+        OCSP_REQUEST_free(req);
+
+        let response_der = http::post_bytes(&responder_url, "application/ocsp-request", &request_der)?;
+
+        let mut response_ptr = response_der.as_ptr();
+        let resp = d2i_OCSP_RESPONSE(ptr::null_mut(), &mut response_ptr, response_der.len() as c_long);
+        if resp.is_null() {
+            return Err("could not parse OCSP response".into());
+        }
+        if OCSP_response_status(resp) != OCSP_RESPONSE_STATUS_SUCCESSFUL {
+            OCSP_RESPONSE_free(resp);
+            return Err("OCSP responder did not return a successful response".into());
+        }
+        let bs = OCSP_response_get1_basic(resp);
+        OCSP_RESPONSE_free(resp);
+        if bs.is_null() {
+            return Err("OCSP response had no basic response".into());
+        }
+
+        let lookup_cid = OCSP_cert_to_id(dgst, cert.as_ptr(), issuer.as_ptr());
+        if lookup_cid.is_null() {
+            OCSP_BASICRESP_free(bs);
+            return Err("could not build OCSP certificate id".into());
+        }
+        let mut status: c_int = V_OCSP_CERTSTATUS_GOOD;
+        let mut reason: c_int = 0;
+        let found = OCSP_resp_find_status(
+            bs,
+            lookup_cid,
+            &mut status,
+            &mut reason,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+        );
+        OCSP_CERTID_free(lookup_cid);
+        OCSP_BASICRESP_free(bs);
+
+        if found == 0 {
+            return Err("OCSP response did not cover this certificate".into());
+        }
+        Ok(if status == V_OCSP_CERTSTATUS_REVOKED { RevocationStatus::Revoked } else { RevocationStatus::Good })
+    }
+}
+
+/// Checks the CRL Distribution Points on `cert` in order; the first one that can be
+/// fetched and parsed decides the result. A distribution point that can't be fetched or parsed is
+/// skipped rather than treated as a failure, since CRLs are mirrored across multiple
+/// URLs precisely so one being down doesn't matter. Falls back to an OCSP lookup (the
+/// other half of "CRL/OCSP revocation verification") when the certificate has no usable
+/// CRL Distribution Point at all.
+pub fn check(cert: &X509) -> Result<RevocationStatus> {
+    let serial = cert.serial_number().to_bn()?.to_vec();
+
+    for url in crl_distribution_points(cert) {
+        if let Ok(revoked) = fetch_revoked_serials(&url) {
+            if revoked.iter().any(|s| strip_der_sign_byte(s) == serial.as_slice()) {
+                return Ok(RevocationStatus::Revoked);
+            }
+            return Ok(RevocationStatus::Good);
+        }
+    }
+
+    check_ocsp(cert).or(Ok(RevocationStatus::Unknown))
+}
+
+/// Convenience wrapper for the `check` subcommand: reads a PEM certificate from disk and
+/// checks it against its own CRL distribution points (falling back to OCSP).
+pub fn check_file<P: AsRef<::std::path::Path>>(path: P) -> Result<RevocationStatus> {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut content = Vec::new();
+    File::open(path)?.read_to_end(&mut content)?;
+    let cert = X509::from_pem(&content)?;
+    check(&cert)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_der_sign_byte, uris_from_der};
+
+    #[test]
+    fn uris_from_der_reads_short_form_length() {
+        let mut der = vec![0x86, 5];
+        der.extend_from_slice(b"hello");
+        assert_eq!(uris_from_der(&der), vec!["hello".to_owned()]);
+    }
+
+    #[test]
+    fn uris_from_der_reads_long_form_length() {
+        let url: String = ::std::iter::repeat('a').take(130).collect();
+        let mut der = vec![0x86, 0x81, 130];
+        der.extend_from_slice(url.as_bytes());
+        assert_eq!(uris_from_der(&der), vec![url]);
+    }
+
+    #[test]
+    fn uris_from_der_finds_multiple_entries() {
+        let mut der = vec![0x86, 3];
+        der.extend_from_slice(b"one");
+        der.push(0x86);
+        der.push(3);
+        der.extend_from_slice(b"two");
+        assert_eq!(uris_from_der(&der), vec!["one".to_owned(), "two".to_owned()]);
+    }
+
+    #[test]
+    fn strip_der_sign_byte_removes_leading_zero() {
+        assert_eq!(strip_der_sign_byte(&[0x00, 0x01, 0x02]), &[0x01, 0x02]);
+    }
+
+    #[test]
+    fn strip_der_sign_byte_leaves_unsigned_serial_untouched() {
+        assert_eq!(strip_der_sign_byte(&[0x01, 0x02]), &[0x01, 0x02]);
+    }
+
+    #[test]
+    fn strip_der_sign_byte_leaves_lone_zero_byte_untouched() {
+        assert_eq!(strip_der_sign_byte(&[0x00]), &[0x00]);
+    }
+}
@@ -0,0 +1,107 @@
+//! A minimal built-in HTTP-01 challenge responder (`--standalone` mode), for hosts that
+//! have no existing webroot to drop `.well-known/acme-challenge` files into.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use error::Result;
+
+const PATH_PREFIX: &'static str = "/.well-known/acme-challenge/";
+
+/// A tiny HTTP server that answers HTTP-01 validation requests directly, without
+/// involving an external web server or a shared webroot directory. Callers register a
+/// `(token, key_authorization)` pair before triggering validation and unregister it
+/// once the authorization is valid.
+pub struct StandaloneServer {
+    challenges: Arc<RwLock<HashMap<String, String>>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StandaloneServer {
+    /// Binds `addr` (typically `0.0.0.0:80`) and starts serving in a background thread.
+    pub fn start(addr: &str) -> Result<StandaloneServer> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        let challenges: Arc<RwLock<HashMap<String, String>>> = Arc::new(RwLock::new(HashMap::new()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread_challenges = challenges.clone();
+        let thread_shutdown = shutdown.clone();
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => handle_connection(stream, &thread_challenges),
+                    Err(_) => thread::sleep(Duration::from_millis(50)),
+                }
+            }
+        });
+
+        Ok(StandaloneServer { challenges: challenges, shutdown: shutdown, handle: Some(handle) })
+    }
+
+    /// Registers the key authorization a validation request for `token` should receive.
+    pub fn register(&self, token: &str, key_authorization: &str) {
+        self.challenges.write().unwrap().insert(token.to_owned(), key_authorization.to_owned());
+    }
+
+    /// Removes a token once its challenge has been validated (or abandoned).
+    pub fn unregister(&self, token: &str) {
+        self.challenges.write().unwrap().remove(token);
+    }
+
+    /// Signals the listener thread to stop and waits for it to exit.
+    pub fn stop(mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for StandaloneServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, challenges: &Arc<RwLock<HashMap<String, String>>>) {
+    let mut buf = [0u8; 1024];
+    let n = match stream.read(&mut buf) {
+        Ok(n) => n,
+        Err(_) => return,
+    };
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("");
+
+    let response = if path.starts_with(PATH_PREFIX) {
+        let token = &path[PATH_PREFIX.len()..];
+        match challenges.read().unwrap().get(token) {
+            Some(key_authorization) => format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+                key_authorization.len(),
+                key_authorization
+            ),
+            None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_owned(),
+        }
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_owned()
+    };
+
+    let _ = stream.write_all(response.as_bytes());
+}